@@ -9,6 +9,31 @@ pub fn parse_color(s: &str) -> Option<Color> {
     }
 }
 
+/// `#rrggbb` for a color, using the common xterm values for named colors
+pub fn to_hex(color: Color) -> Option<String> {
+    let (r, g, b) = match color {
+        Color::Rgb { r, g, b } => (r, g, b),
+        Color::Black => (0x00, 0x00, 0x00),
+        Color::DarkRed => (0xcd, 0x00, 0x00),
+        Color::DarkGreen => (0x00, 0xcd, 0x00),
+        Color::DarkYellow => (0xcd, 0xcd, 0x00),
+        Color::DarkBlue => (0x00, 0x00, 0xee),
+        Color::DarkMagenta => (0xcd, 0x00, 0xcd),
+        Color::DarkCyan => (0x00, 0xcd, 0xcd),
+        Color::Grey => (0xe5, 0xe5, 0xe5),
+        Color::DarkGrey => (0x7f, 0x7f, 0x7f),
+        Color::Red => (0xff, 0x00, 0x00),
+        Color::Green => (0x00, 0xff, 0x00),
+        Color::Yellow => (0xff, 0xff, 0x00),
+        Color::Blue => (0x5c, 0x5c, 0xff),
+        Color::Magenta => (0xff, 0x00, 0xff),
+        Color::Cyan => (0x00, 0xff, 0xff),
+        Color::White => (0xff, 0xff, 0xff),
+        _ => return None,
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
 pub fn parse_hex(s: &str) -> Option<Color> {
     let hex = s.strip_prefix('#')?;
     if hex.len() != 6 {
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
-use crate::stats::{StatId, StatIdOrTitle};
+use crate::stats::{StatId, StatIdOrTitle, template_placeholders};
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Default)]
 #[serde(rename_all = "snake_case")]
@@ -44,22 +44,57 @@ pub struct Config {
     pub cache: CacheConfig,
     #[serde(default)]
     pub disk: DiskConfig,
+    #[serde(default)]
+    pub aur: AurConfig,
+    #[serde(default)]
+    pub flatpak: FlatpakConfig,
+    #[serde(default)]
+    pub upgrade: UpgradeConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub largest_packages: LargestPackagesConfig,
+    #[serde(default)]
+    pub recent_packages: RecentPackagesConfig,
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+}
+
+#[derive(Deserialize, Default, Clone)]
+pub struct PerformanceConfig {
+    /// Milliseconds to wait for stats before rendering; 0 waits for all of them
+    #[serde(default)]
+    pub budget_ms: u64,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct CacheConfig {
     #[serde(default = "default_ttl")]
     pub ttl_minutes: u32,
+    #[serde(default = "default_aur_ttl")]
+    pub aur_ttl_minutes: u32,
+    #[serde(default = "default_security_ttl")]
+    pub security_ttl_minutes: u32,
 }
 
 fn default_ttl() -> u32 {
     15
 }
 
+fn default_aur_ttl() -> u32 {
+    60
+}
+
+fn default_security_ttl() -> u32 {
+    180
+}
+
 impl Default for CacheConfig {
     fn default() -> Self {
         CacheConfig {
             ttl_minutes: default_ttl(),
+            aur_ttl_minutes: default_aur_ttl(),
+            security_ttl_minutes: default_security_ttl(),
         }
     }
 }
@@ -82,6 +117,404 @@ impl Default for DiskConfig {
     }
 }
 
+#[derive(Deserialize, Clone)]
+pub struct LargestPackagesConfig {
+    #[serde(default = "default_largest_count")]
+    pub count: usize,
+    /// Count dependencies nothing else needs together with their package
+    #[serde(default)]
+    pub exclusive_deps: bool,
+}
+
+fn default_largest_count() -> usize {
+    5
+}
+
+impl Default for LargestPackagesConfig {
+    fn default() -> Self {
+        LargestPackagesConfig {
+            count: default_largest_count(),
+            exclusive_deps: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RecentPackagesConfig {
+    #[serde(default = "default_recent_count")]
+    pub count: usize,
+    /// Only list changes from the last N days
+    #[serde(default)]
+    pub days: Option<u32>,
+}
+
+fn default_recent_count() -> usize {
+    10
+}
+
+impl Default for RecentPackagesConfig {
+    fn default() -> Self {
+        RecentPackagesConfig {
+            count: default_recent_count(),
+            days: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AurConfig {
+    #[serde(default = "default_aur_helper")]
+    pub helper: String,
+    #[serde(default)]
+    pub helper_args: Vec<String>,
+    #[serde(default = "default_aur_rpc_url")]
+    pub rpc_url: String,
+    #[serde(default = "default_aur_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_aur_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_aur_helper() -> String {
+    "yay".to_string()
+}
+
+fn default_aur_rpc_url() -> String {
+    "https://aur.archlinux.org/rpc/".to_string()
+}
+
+fn default_aur_timeout() -> u64 {
+    10
+}
+
+fn default_aur_batch_size() -> usize {
+    100
+}
+
+impl Default for AurConfig {
+    fn default() -> Self {
+        AurConfig {
+            helper: default_aur_helper(),
+            helper_args: Vec::new(),
+            rpc_url: default_aur_rpc_url(),
+            timeout_secs: default_aur_timeout(),
+            batch_size: default_aur_batch_size(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct FlatpakConfig {
+    #[serde(default = "default_flatpak_path")]
+    pub path: String,
+    #[serde(default)]
+    pub include_in_upgradable: bool,
+}
+
+fn default_flatpak_path() -> String {
+    "flatpak".to_string()
+}
+
+impl Default for FlatpakConfig {
+    fn default() -> Self {
+        FlatpakConfig {
+            path: default_flatpak_path(),
+            include_in_upgradable: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SecurityConfig {
+    #[serde(default = "default_security_url")]
+    pub url: String,
+    #[serde(default = "default_security_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_security_url() -> String {
+    "https://security.archlinux.org/all.json".to_string()
+}
+
+fn default_security_timeout() -> u64 {
+    10
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        SecurityConfig {
+            url: default_security_url(),
+            timeout_secs: default_security_timeout(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct UpgradeConfig {
+    /// Steps run after -Su: "flatpak" or a shell command
+    #[serde(default)]
+    pub post_hooks: Vec<String>,
+    /// Upgrade archlinux-keyring on its own before -Su
+    #[serde(default)]
+    pub keyring_first: bool,
+    /// How long to wait for another pacman to release the database lock
+    #[serde(default = "default_lock_timeout")]
+    pub lock_timeout_secs: u64,
+}
+
+fn default_lock_timeout() -> u64 {
+    120
+}
+
+impl Default for UpgradeConfig {
+    fn default() -> Self {
+        UpgradeConfig {
+            post_hooks: Vec::new(),
+            keyring_first: false,
+            lock_timeout_secs: default_lock_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeUnits {
+    /// Powers of 1024: KiB, MiB, GiB
+    #[default]
+    Iec,
+    /// Powers of 1000: kB, MB, GB
+    Si,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SizeConfig {
+    #[serde(default)]
+    pub units: SizeUnits,
+    /// Decimal places once a size is scaled past bytes
+    #[serde(default = "default_size_precision")]
+    pub precision: usize,
+}
+
+fn default_size_precision() -> usize {
+    2
+}
+
+impl Default for SizeConfig {
+    fn default() -> Self {
+        SizeConfig {
+            units: SizeUnits::default(),
+            precision: default_size_precision(),
+        }
+    }
+}
+
+/// How a timestamp stat is shown
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum TimestampFormat {
+    /// `3 days 4 hours`
+    #[default]
+    Relative,
+    /// `3d 4h`
+    Compact,
+    /// `2024-05-01 14:32`
+    Absolute,
+    /// `2024-05-01 14:32 (3 days 4 hours)`
+    Both,
+    /// A strftime pattern
+    Custom(String),
+}
+
+impl TimestampFormat {
+    /// "relative", "compact", "absolute", "both" or a strftime pattern such
+    /// as `"%a %d %b %H:%M"`. An invalid pattern is relative with a warning.
+    pub fn parse(value: &str, key: &str) -> Self {
+        match value {
+            "relative" => TimestampFormat::Relative,
+            "compact" => TimestampFormat::Compact,
+            "absolute" => TimestampFormat::Absolute,
+            "both" => TimestampFormat::Both,
+            pattern => {
+                let invalid = chrono::format::StrftimeItems::new(pattern)
+                    .any(|item| matches!(item, chrono::format::Item::Error));
+                if invalid || !pattern.contains('%') {
+                    crate::log::warn(&format!(
+                        "Invalid [display.timestamps] {} format '{}', using relative",
+                        key, pattern
+                    ));
+                    TimestampFormat::Relative
+                } else {
+                    TimestampFormat::Custom(pattern.to_string())
+                }
+            }
+        }
+    }
+
+    /// Whether the text is a duration, read as "… ago"
+    pub fn is_relative(&self) -> bool {
+        matches!(self, TimestampFormat::Relative | TimestampFormat::Compact)
+    }
+}
+
+/// Stats whose value is a point in time
+pub const TIMESTAMP_STATS: &[StatId] =
+    &[StatId::LastUpdate, StatId::RecentPackages, StatId::DbLock];
+
+/// `[display.timestamps]`: a `format` for every timestamp stat, and
+/// overrides by stat key, e.g. `recent_packages = "compact"`
+#[derive(Debug, Deserialize, Clone)]
+pub struct TimestampsConfig {
+    #[serde(default = "default_timestamp_format")]
+    pub format: String,
+    #[serde(flatten)]
+    pub stats: HashMap<String, String>,
+}
+
+fn default_timestamp_format() -> String {
+    "relative".to_string()
+}
+
+impl Default for TimestampsConfig {
+    fn default() -> Self {
+        TimestampsConfig {
+            format: default_timestamp_format(),
+            stats: HashMap::new(),
+        }
+    }
+}
+
+/// Deprecated `[display.last_update]`, superseded by `[display.timestamps]`
+#[derive(Debug, Deserialize, Clone)]
+pub struct TimestampConfig {
+    pub format: String,
+}
+
+/// A `[display.thresholds.<stat>]` rule. Values are compared at or above
+/// `warn` and `critical`, in the stat's own unit: a count, a duration like
+/// `14d`, a size like `5GiB` or a percentage. Unset fields fall back to the
+/// built-in rule for the stat, if any.
+#[derive(Deserialize, Clone, Default)]
+pub struct ThresholdRule {
+    #[serde(default)]
+    pub warn: Option<String>,
+    #[serde(default)]
+    pub critical: Option<String>,
+    #[serde(default)]
+    pub warn_color: Option<String>,
+    #[serde(default)]
+    pub critical_color: Option<String>,
+    /// Color below `warn`; unset keeps the stat color
+    #[serde(default)]
+    pub ok_color: Option<String>,
+}
+
+fn default_warn_color() -> String {
+    "bright_yellow".to_string()
+}
+
+fn default_critical_color() -> String {
+    "bright_red".to_string()
+}
+
+impl ThresholdRule {
+    /// Built-in rules, used unless the config sets its own for the stat
+    fn builtin(stat_id: StatId) -> Option<Self> {
+        let rule = |warn: Option<&str>, critical: Option<&str>| ThresholdRule {
+            warn: warn.map(String::from),
+            critical: critical.map(String::from),
+            warn_color: None,
+            critical_color: None,
+            ok_color: Some("bright_green".to_string()),
+        };
+        match stat_id {
+            StatId::Disk => Some(rule(Some("70%"), Some("90%"))),
+            StatId::MirrorHealth => Some(rule(None, None)),
+            _ => None,
+        }
+    }
+
+    /// This rule with its unset fields taken from `base`
+    fn or(self, base: ThresholdRule) -> Self {
+        ThresholdRule {
+            warn: self.warn.or(base.warn),
+            critical: self.critical.or(base.critical),
+            warn_color: self.warn_color.or(base.warn_color),
+            critical_color: self.critical_color.or(base.critical_color),
+            ok_color: self.ok_color.or(base.ok_color),
+        }
+    }
+}
+
+/// A threshold rule with its values parsed for the stat
+#[derive(Clone)]
+pub struct Thresholds {
+    pub warn: Option<f64>,
+    pub critical: Option<f64>,
+    pub warn_color: String,
+    pub critical_color: String,
+    pub ok_color: String,
+}
+
+/// Which side of a stat's thresholds a value falls on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdLevel {
+    Ok,
+    Warn,
+    Critical,
+}
+
+impl ThresholdLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThresholdLevel::Ok => "ok",
+            ThresholdLevel::Warn => "warn",
+            ThresholdLevel::Critical => "critical",
+        }
+    }
+}
+
+impl Thresholds {
+    /// Warn from `warn` up, critical only above `critical`, as the disk
+    /// colors always were
+    pub fn level(&self, value: f64) -> ThresholdLevel {
+        if self.critical.is_some_and(|c| value > c) {
+            ThresholdLevel::Critical
+        } else if self.warn.is_some_and(|w| value >= w) {
+            ThresholdLevel::Warn
+        } else {
+            ThresholdLevel::Ok
+        }
+    }
+
+    /// Color name for a stat value, None to keep the stat color
+    pub fn color_for(&self, value: f64) -> Option<&str> {
+        match self.level(value) {
+            ThresholdLevel::Critical => Some(&self.critical_color),
+            ThresholdLevel::Warn => Some(&self.warn_color),
+            ThresholdLevel::Ok if self.ok_color.is_empty() => None,
+            ThresholdLevel::Ok => Some(&self.ok_color),
+        }
+    }
+}
+
+/// A `[display.custom.<name>]` stat whose value is a command's output
+#[derive(Deserialize, Clone)]
+pub struct CustomStatConfig {
+    /// Run with `sh -c`; the first line of stdout is the value
+    pub command: String,
+    /// Defaults to the stat's name
+    #[serde(default)]
+    pub label: String,
+    #[serde(default = "default_custom_timeout")]
+    pub timeout_ms: u64,
+    /// Minutes to reuse the last output; 0 runs the command every time
+    #[serde(default)]
+    pub cache_ttl: u32,
+}
+
+fn default_custom_timeout() -> u64 {
+    1000
+}
+
 #[derive(Deserialize, Clone)]
 pub struct PaletteConfig {
     #[serde(default = "default_palette_style")]
@@ -241,6 +674,23 @@ pub struct DisplayConfig {
     #[serde(default)]
     pub image: String,
 
+    /// Show installed as e.g. "1234 (312 explicit)"
+    #[serde(default)]
+    pub installed_breakdown: bool,
+
+    /// "auto" follows LC_ALL/LC_MESSAGES/LANG; or a name like "de" or "pt_BR"
+    #[serde(default = "default_locale")]
+    pub locale: String,
+
+    #[serde(default)]
+    pub size: SizeConfig,
+
+    #[serde(default)]
+    pub timestamps: TimestampsConfig,
+
+    #[serde(default)]
+    pub last_update: Option<TimestampConfig>,
+
     #[serde(default)]
     pub glyph: GlyphConfig,
 
@@ -258,6 +708,20 @@ pub struct DisplayConfig {
 
     #[serde(default)]
     pub titles: HashMap<String, TitleConfig>,
+
+    #[serde(default)]
+    pub custom: HashMap<String, CustomStatConfig>,
+
+    /// Value templates by stat key, e.g. `"{count} pkgs / {size}"`
+    #[serde(default)]
+    pub formats: HashMap<String, String>,
+
+    #[serde(default)]
+    pub thresholds: HashMap<String, ThresholdRule>,
+}
+
+fn default_locale() -> String {
+    "auto".to_string()
 }
 
 fn default_ascii() -> String {
@@ -293,12 +757,20 @@ impl Default for DisplayConfig {
             ascii: default_ascii(),
             ascii_color: default_ascii_color(),
             image: String::new(),
+            installed_breakdown: false,
+            locale: default_locale(),
+            size: SizeConfig::default(),
+            timestamps: TimestampsConfig::default(),
+            last_update: None,
             glyph: GlyphConfig::default(),
             palette: PaletteConfig::default(),
             colors: ColorsConfig::default(),
             labels: HashMap::new(),
             title: TitleConfig::default(),
             titles: HashMap::new(),
+            custom: HashMap::new(),
+            formats: HashMap::new(),
+            thresholds: HashMap::new(),
         }
     }
 }
@@ -316,6 +788,128 @@ impl DisplayConfig {
             })
             .collect()
     }
+
+    /// `[display.formats]` by stat. Unknown stats and placeholders are
+    /// logged as warnings.
+    pub fn parsed_formats(&self) -> HashMap<StatId, String> {
+        let mut formats = HashMap::new();
+        for (key, template) in &self.formats {
+            let stat_id = match StatId::parse(key) {
+                Ok(StatIdOrTitle::Stat(stat_id)) => stat_id,
+                _ => {
+                    crate::log::warn(&format!("[display.formats] unknown stat: {}", key));
+                    continue;
+                }
+            };
+            for name in template_placeholders(template) {
+                if !stat_id.placeholders().contains(&name) {
+                    crate::log::warn(&format!(
+                        "[display.formats] {}: unknown placeholder {{{}}}, expected one of: {}",
+                        key,
+                        name,
+                        stat_id.placeholders().join(", ")
+                    ));
+                }
+            }
+            formats.insert(stat_id, template.clone());
+        }
+        formats
+    }
+
+    /// `[display.timestamps]` format for each timestamp stat. Unknown stats
+    /// are logged as warnings.
+    pub fn parsed_timestamps(&self) -> HashMap<StatId, TimestampFormat> {
+        for key in self.timestamps.stats.keys() {
+            let known = matches!(
+                StatId::parse(key),
+                Ok(StatIdOrTitle::Stat(id)) if TIMESTAMP_STATS.contains(&id)
+            );
+            if !known {
+                crate::log::warn(&format!(
+                    "[display.timestamps] {} is not a timestamp stat",
+                    key
+                ));
+            }
+        }
+        if self.last_update.is_some() {
+            crate::log::warn(
+                "Deprecated: [display.last_update] config. Use [display.timestamps] instead.",
+            );
+        }
+
+        TIMESTAMP_STATS
+            .iter()
+            .map(|id| {
+                let key = id.config_key();
+                let legacy = match id {
+                    StatId::LastUpdate => self.last_update.as_ref().map(|t| &t.format),
+                    _ => None,
+                };
+                let value = self
+                    .timestamps
+                    .stats
+                    .get(key)
+                    .or(legacy)
+                    .unwrap_or(&self.timestamps.format);
+                (*id, TimestampFormat::parse(value, key))
+            })
+            .collect()
+    }
+
+    /// `[display.thresholds]` by stat, on top of the built-in disk and
+    /// mirror_health rules. Rules that don't parse are logged and skipped.
+    pub fn parsed_thresholds(&self) -> HashMap<StatId, Thresholds> {
+        let mut rules: HashMap<StatId, ThresholdRule> = [StatId::Disk, StatId::MirrorHealth]
+            .into_iter()
+            .filter_map(|id| ThresholdRule::builtin(id).map(|rule| (id, rule)))
+            .collect();
+        for (key, rule) in &self.thresholds {
+            match StatId::parse(key) {
+                Ok(StatIdOrTitle::Stat(stat_id)) => {
+                    let base = rules.remove(&stat_id).unwrap_or_default();
+                    rules.insert(stat_id, rule.clone().or(base));
+                }
+                _ => crate::log::warn(&format!("[display.thresholds] unknown stat: {}", key)),
+            }
+        }
+
+        let mut thresholds = HashMap::new();
+        for (stat_id, rule) in rules {
+            let Some(kind) = stat_id.metric_kind() else {
+                crate::log::warn(&format!(
+                    "[display.thresholds] {} has no numeric value",
+                    stat_id.config_key()
+                ));
+                continue;
+            };
+            let parse = |value: &Option<String>| match value {
+                Some(v) => kind.parse_threshold(v).map(Some),
+                None => Ok(None),
+            };
+            match (parse(&rule.warn), parse(&rule.critical)) {
+                (Ok(warn), Ok(critical)) => {
+                    thresholds.insert(
+                        stat_id,
+                        Thresholds {
+                            warn,
+                            critical,
+                            warn_color: rule.warn_color.unwrap_or_else(default_warn_color),
+                            critical_color: rule
+                                .critical_color
+                                .unwrap_or_else(default_critical_color),
+                            ok_color: rule.ok_color.unwrap_or_default(),
+                        },
+                    );
+                }
+                (Err(e), _) | (_, Err(e)) => crate::log::warn(&format!(
+                    "[display.thresholds] {}: {}",
+                    stat_id.config_key(),
+                    e
+                )),
+            }
+        }
+        thresholds
+    }
 }
 
 impl Config {
@@ -422,3 +1016,94 @@ impl Config {
         Some(new_config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pacman::PacmanStats;
+    use crate::stats::RenderContext;
+
+    #[test]
+    fn test_timestamps_shared_format_and_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+            [display.timestamps]
+            format = "compact"
+            recent_packages = "absolute"
+
+            [display.last_update]
+            format = "both"
+            "#,
+        )
+        .unwrap();
+        let formats = config.display.parsed_timestamps();
+        assert_eq!(formats[&StatId::DbLock], TimestampFormat::Compact);
+        assert_eq!(formats[&StatId::RecentPackages], TimestampFormat::Absolute);
+        // The deprecated section still applies to last_update
+        assert_eq!(formats[&StatId::LastUpdate], TimestampFormat::Both);
+    }
+
+    #[test]
+    fn test_threshold_rules_pick_colors() {
+        let config: Config = toml::from_str(
+            r#"
+            [display.thresholds.upgradable]
+            warn = "50"
+            critical = "100"
+
+            [display.thresholds.last_update]
+            warn = "14d"
+
+            [display.thresholds.cache_size]
+            critical = "5GiB"
+            critical_color = "magenta"
+
+            [display.thresholds.disk]
+            critical = "95%"
+            "#,
+        )
+        .unwrap();
+        let thresholds = config.display.parsed_thresholds();
+        let ctx = RenderContext::new(&config);
+        let color = |id: StatId, stats: &PacmanStats| {
+            thresholds[&id]
+                .color_for(id.metric(stats, &ctx).unwrap())
+                .map(String::from)
+        };
+
+        let stats = PacmanStats {
+            total_upgradable: Some(120),
+            days_since_last_update: Some(3 * 86400),
+            cache_size_bytes: Some(6 << 30),
+            disk_used_bytes: Some(75),
+            disk_total_bytes: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(
+            color(StatId::Upgradable, &stats).as_deref(),
+            Some("bright_red")
+        );
+        assert_eq!(color(StatId::LastUpdate, &stats), None);
+        assert_eq!(color(StatId::CacheSize, &stats).as_deref(), Some("magenta"));
+        // The built-in disk warn level and ok color still apply
+        assert_eq!(
+            color(StatId::Disk, &stats).as_deref(),
+            Some("bright_yellow")
+        );
+        let stats = PacmanStats {
+            disk_used_bytes: Some(20),
+            ..stats
+        };
+        assert_eq!(color(StatId::Disk, &stats).as_deref(), Some("bright_green"));
+    }
+
+    #[test]
+    fn test_builtin_disk_threshold_boundaries() {
+        let thresholds = Config::default().display.parsed_thresholds();
+        let disk = &thresholds[&StatId::Disk];
+        assert_eq!(disk.level(69.9), ThresholdLevel::Ok);
+        assert_eq!(disk.level(70.0), ThresholdLevel::Warn);
+        assert_eq!(disk.level(90.0), ThresholdLevel::Warn);
+        assert_eq!(disk.level(90.1), ThresholdLevel::Critical);
+    }
+}
@@ -0,0 +1,369 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+use crate::config::Config;
+use crate::stats::StatId;
+
+/// Which counts take the singular form of a unit
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PluralRule {
+    /// Only 1 is singular (English, German)
+    #[default]
+    One,
+    /// 0 and 1 are singular (French, Portuguese)
+    ZeroOne,
+}
+
+impl PluralRule {
+    /// `int` is the integer part; `fraction` whether decimals are shown,
+    /// which makes "1.0" plural in English but not in French
+    fn is_singular(&self, int: i64, fraction: bool) -> bool {
+        match self {
+            PluralRule::One => int == 1 && !fraction,
+            PluralRule::ZeroOne => int == 0 || int == 1,
+        }
+    }
+}
+
+/// A translation file: built in under src/locales, or a user file at
+/// ~/.config/pacfetch/locales/<lang>.toml. Every key is optional.
+#[derive(Deserialize, Default)]
+struct LocaleFile {
+    thousands_separator: Option<String>,
+    decimal_mark: Option<String>,
+    plural: Option<PluralRule>,
+    #[serde(default)]
+    units: HashMap<String, [String; 2]>,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    #[serde(default)]
+    messages: HashMap<String, String>,
+}
+
+/// Value phrases in English, with `{name}` placeholders
+const MESSAGES: &[(&str, &str)] = &[
+    ("installed_explicit", "{total} ({explicit} explicit)"),
+    ("upgradable_repo", "{count} repo"),
+    (
+        "flatpak_breakdown",
+        "{count} ({system} system, {user} user)",
+    ),
+    ("largest_in_top", "{size} in top {count}"),
+    ("db_lock_held", "Held by {holders} for {age}"),
+    ("db_lock_stale", "Stale ({age} old, no pacman running)"),
+    ("db_lock_held_since", "Held by {holders} since {age}"),
+    (
+        "db_lock_stale_since",
+        "Stale (since {age}, no pacman running)",
+    ),
+    ("ago", "{time} ago"),
+    ("mirror_ok", "OK"),
+    ("mirror_last_sync", "last sync {hours}"),
+    ("mirror_unchecked", "could not check sync status"),
+    ("mirror_not_found", "no mirror found"),
+    ("err", "Err"),
+    ("timed_out", "timed out"),
+    ("upgrade_check_failed", "upgrade check failed"),
+    ("invalid_signature", "invalid signature"),
+    ("invalid_signature_repos", "invalid signature: {repos}"),
+    ("aur_unreachable", "AUR unreachable"),
+    ("aur_invalid_signature", "AUR: invalid signature"),
+    ("flatpak_check_failed", "Flatpak check failed"),
+    ("upgradable_aur", "{count} AUR"),
+    ("upgradable_flatpak", "{count} Flatpak"),
+    ("vuln_fixed_by_upgrade", "{count} fixed by upgrade"),
+    ("vuln_fix_pending", "{count} fix pending"),
+    ("vuln_no_fix", "{count} no fix yet"),
+    ("change_installed", "installed"),
+    ("change_upgraded", "upgraded"),
+    ("change_downgraded", "downgraded"),
+    ("change_reinstalled", "reinstalled"),
+    ("keyring_outdated", "Outdated ({installed} -> {available})"),
+    ("keyring_up_to_date", "Up to date ({installed})"),
+    ("db_lock_unlocked", "Unlocked"),
+];
+
+/// Labels, duration units and number formatting for one language.
+/// The default is the C locale: English, no digit grouping.
+#[derive(Debug, Clone)]
+pub struct Locale {
+    thousands_separator: String,
+    decimal_mark: String,
+    plural: PluralRule,
+    /// unit -> [singular, plural]
+    units: HashMap<String, [String; 2]>,
+    /// stat key -> label
+    labels: HashMap<String, String>,
+    /// message key -> phrase, see MESSAGES
+    messages: HashMap<String, String>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        let units = [
+            ("second", "seconds"),
+            ("minute", "minutes"),
+            ("hour", "hours"),
+            ("day", "days"),
+        ]
+        .into_iter()
+        .map(|(one, other)| (one.to_string(), [one.to_string(), other.to_string()]))
+        .collect();
+
+        Locale {
+            thousands_separator: String::new(),
+            decimal_mark: ".".to_string(),
+            plural: PluralRule::One,
+            units,
+            labels: HashMap::new(),
+            messages: MESSAGES
+                .iter()
+                .map(|(key, text)| (key.to_string(), text.to_string()))
+                .collect(),
+        }
+    }
+}
+
+fn builtin(lang: &str) -> Option<&'static str> {
+    match lang {
+        "en" => Some(include_str!("locales/en.toml")),
+        "de" => Some(include_str!("locales/de.toml")),
+        "pt" => Some(include_str!("locales/pt.toml")),
+        _ => None,
+    }
+}
+
+/// A locale category from the environment, e.g. "de_DE.UTF-8" for
+/// LC_MESSAGES or LC_NUMERIC
+fn env_locale(category: &str) -> String {
+    ["LC_ALL", category, "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+        .unwrap_or_else(|| "C".to_string())
+}
+
+/// The built-in translation for a locale name, then user files for its
+/// language and region
+fn locale_files(name: &str) -> Vec<LocaleFile> {
+    // "pt_BR.UTF-8@euro" -> tag "pt_BR", lang "pt"
+    let tag = name.split(['.', '@']).next().unwrap_or_default();
+    let lang = tag
+        .split(['_', '-'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let mut files = Vec::new();
+    if let Some(contents) = builtin(&lang) {
+        match toml::from_str(contents) {
+            Ok(file) => files.push(file),
+            Err(e) => crate::log::warn(&format!("Built-in locale '{}': {}", lang, e)),
+        }
+    }
+
+    let dir = Config::config_path().and_then(|p| p.parent().map(|d| d.join("locales")));
+    let mut names = vec![lang.clone()];
+    if tag != lang {
+        names.push(tag.to_string());
+    }
+    for name in names {
+        let Some(path) = dir.as_ref().map(|d| d.join(format!("{}.toml", name))) else {
+            continue;
+        };
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        match toml::from_str(&contents) {
+            Ok(file) => files.push(file),
+            Err(e) => crate::log::warn(&format!("Invalid {}: {}", path.display(), e)),
+        }
+    }
+    files
+}
+
+impl Locale {
+    /// Load a locale by name ("de", "pt_BR") or from the environment for
+    /// "auto", where text follows LC_MESSAGES and number formatting
+    /// LC_NUMERIC. User files for the language, then the region, are
+    /// applied over the built-in translation.
+    pub fn load(setting: &str) -> Self {
+        let (messages, numeric) = match setting {
+            "" | "auto" => (env_locale("LC_MESSAGES"), env_locale("LC_NUMERIC")),
+            name => (name.to_string(), name.to_string()),
+        };
+
+        let mut locale = Locale::default();
+        for file in locale_files(&messages) {
+            locale.merge_text(file);
+        }
+        for file in locale_files(&numeric) {
+            locale.merge_numbers(&file);
+        }
+        locale
+    }
+
+    fn merge_text(&mut self, file: LocaleFile) {
+        if let Some(plural) = file.plural {
+            self.plural = plural;
+        }
+        self.units.extend(file.units);
+        self.labels.extend(file.labels);
+        self.messages.extend(file.messages);
+    }
+
+    fn merge_numbers(&mut self, file: &LocaleFile) {
+        if let Some(sep) = &file.thousands_separator {
+            self.thousands_separator = sep.clone();
+        }
+        if let Some(mark) = &file.decimal_mark {
+            self.decimal_mark = mark.clone();
+        }
+    }
+
+    pub fn label(&self, stat_id: StatId) -> String {
+        self.labels
+            .get(stat_id.config_key())
+            .cloned()
+            .unwrap_or_else(|| stat_id.label().to_string())
+    }
+
+    /// An integer with thousands separators, e.g. `12.345`
+    pub fn integer(&self, n: i64) -> String {
+        let digits = n.unsigned_abs().to_string();
+        let mut grouped = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(&self.thousands_separator);
+            }
+            grouped.push(c);
+        }
+        if n < 0 {
+            format!("-{}", grouped)
+        } else {
+            grouped
+        }
+    }
+
+    /// A decimal with thousands separators and the locale's decimal mark
+    pub fn decimal(&self, value: f64, precision: usize) -> String {
+        let formatted = format!("{:.*}", precision, value.abs());
+        let (int, frac) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let sign = if value < 0.0 && formatted.chars().any(|c| ('1'..='9').contains(&c)) {
+            "-"
+        } else {
+            ""
+        };
+        let int = self.integer(int.parse().unwrap_or(0));
+        if frac.is_empty() {
+            format!("{}{}", sign, int)
+        } else {
+            format!("{}{}{}{}", sign, int, self.decimal_mark, frac)
+        }
+    }
+
+    /// A message from MESSAGES with its `{name}` placeholders filled in
+    pub fn message(&self, key: &str, args: &[(&str, String)]) -> String {
+        let mut text = self.messages.get(key).cloned().unwrap_or_default();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    /// The singular or plural form of a unit
+    fn unit_form(&self, unit: &str, int: i64, fraction: bool) -> String {
+        match self.units.get(unit) {
+            Some([one, other]) => {
+                if self.plural.is_singular(int, fraction) {
+                    one.clone()
+                } else {
+                    other.clone()
+                }
+            }
+            None => unit.to_string(),
+        }
+    }
+
+    /// `n unit` with the unit's singular or plural form
+    fn count_unit(&self, n: i64, unit: &str) -> String {
+        format!("{} {}", self.integer(n), self.unit_form(unit, n, false))
+    }
+
+    /// A decimal count of a unit, e.g. `1.5 hours`
+    pub fn decimal_unit(&self, value: f64, precision: usize, unit: &str) -> String {
+        // The integer part as displayed, so 0.96 counts as "1.0"
+        let shown = format!("{:.*}", precision, value.abs());
+        let int = shown
+            .split('.')
+            .next()
+            .and_then(|i| i.parse().ok())
+            .unwrap_or(0);
+        let form = self.unit_form(unit, int, precision > 0);
+        format!("{} {}", self.decimal(value, precision), form)
+    }
+
+    /// A duration in its largest units, short form, e.g. `3d 4h`
+    pub fn compact_duration(&self, seconds: i64) -> String {
+        match seconds {
+            ..60 => format!("{}s", seconds),
+            60..3600 => format!("{}m", seconds / 60),
+            3600..86400 => format!("{}h", seconds / 3600),
+            _ => format!(
+                "{}d {}h",
+                self.integer(seconds / 86400),
+                (seconds % 86400) / 3600
+            ),
+        }
+    }
+
+    /// A duration in its largest units, e.g. `3 days 4 hours`
+    pub fn duration(&self, seconds: i64) -> String {
+        if seconds < 60 {
+            return self.count_unit(seconds, "second");
+        }
+        if seconds < 3600 {
+            return self.count_unit(seconds / 60, "minute");
+        }
+        if seconds < 86400 {
+            return self.count_unit(seconds / 3600, "hour");
+        }
+        format!(
+            "{} {}",
+            self.count_unit(seconds / 86400, "day"),
+            self.count_unit((seconds % 86400) / 3600, "hour")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_formats_numbers_and_durations() {
+        let de = Locale::load("de_DE.UTF-8");
+        assert_eq!(de.integer(1234567), "1.234.567");
+        assert_eq!(de.decimal(1536.5, 2), "1.536,50");
+        assert_eq!(de.duration(93600), "1 Tag 2 Stunden");
+        assert_eq!(de.label(StatId::Installed), "Installiert");
+
+        let c = Locale::load("C");
+        assert_eq!(c.integer(1234), "1234");
+        assert_eq!(c.duration(1), "1 second");
+        assert_eq!(c.duration(172800), "2 days 0 hours");
+        assert_eq!(c.decimal_unit(1.0, 0, "hour"), "1 hour");
+        assert_eq!(c.decimal_unit(1.0, 1, "hour"), "1.0 hours");
+        assert_eq!(
+            c.message("db_lock_stale", &[("age", c.duration(120))]),
+            "Stale (2 minutes old, no pacman running)"
+        );
+
+        let pt = Locale::load("pt_BR");
+        assert_eq!(pt.duration(0), "0 segundo");
+        assert_eq!(pt.decimal_unit(1.5, 1, "hour"), "1,5 hora");
+        assert_eq!(pt.decimal_unit(2.5, 1, "hour"), "2,5 horas");
+    }
+}
@@ -1,14 +1,18 @@
 mod color;
 mod config;
+mod locale;
 mod log;
+mod output;
 mod pacman;
 mod stats;
 mod ui;
 mod util;
 
-use clap::{CommandFactory, Parser};
+use clap::{CommandFactory, Parser, ValueEnum};
 use config::Config;
+use output::{BarFormat, OutputFormat};
 use std::fs;
+use std::path::PathBuf;
 
 fn ensure_config_exists() {
     let Some(config_path) = Config::config_path() else {
@@ -33,15 +37,22 @@ Commands:
   -Sy           Sync package databases
   -Su           Upgrade system
   -Syu          Sync databases and upgrade system
-  --yay         Full system + AUR upgrade via yay
-  --paru        Full system + AUR upgrade via paru
+  --aur         Full system + AUR upgrade via the configured AUR helper
+  --yay         Alias for --aur with yay
+  --paru        Alias for --aur with paru
+  -- <ARGS>...  Pass remaining arguments through to the AUR helper
 
 Options:
       --ascii <ASCII>  Use custom ASCII art (path, built-in name, or NONE)
       --color <COLOR>  Override ASCII art color (name, hex #RRGGBB, or none)
       --image <PATH>   Use an image instead of ASCII art
       --json           Output stats as JSON
+      --format <FMT>   Output stats as json, yaml, toml, env or markdown
+      --prometheus <PATH>
+                       Write metrics for node_exporter's textfile collector
+      --bar <BAR>      Status bar module for waybar, i3blocks or polybar
       --local          Use local cached database (skip temp sync)
+      --timeout <MS>   Render after MS milliseconds, marking unfinished stats as timed out
   -d, --debug          Debug mode
   -h, --help           Print help
   -V, --version        Print version")]
@@ -81,15 +92,128 @@ struct Cli {
     #[arg(long = "json", hide = true)]
     json: bool,
 
+    #[arg(long = "format", value_enum, hide = true, conflicts_with = "json")]
+    format: Option<OutputFormat>,
+
+    #[arg(long = "prometheus", hide = true)]
+    prometheus: Option<PathBuf>,
+
+    #[arg(long = "bar", value_enum, hide = true)]
+    bar: Option<BarFormat>,
+
     #[arg(long = "yay", hide = true)]
     yay: bool,
 
     #[arg(long = "paru", hide = true)]
     paru: bool,
+
+    #[arg(long = "aur", hide = true)]
+    aur: bool,
+
+    #[arg(long = "timeout", hide = true)]
+    timeout: Option<u64>,
+
+    #[arg(last = true, hide = true)]
+    helper_args: Vec<String>,
 }
 
 fn is_bare_invocation(cli: &Cli) -> bool {
-    !cli.sync_op && !cli.sync_db && !cli.upgrade && !cli.yay && !cli.paru && !cli.local
+    !cli.sync_op && !cli.sync_db && !cli.upgrade && !cli.yay && !cli.paru && !cli.aur && !cli.local
+}
+
+/// --json, --format, --prometheus or --bar: output for scripts, run unattended
+fn is_machine_output(cli: &Cli) -> bool {
+    cli.json || cli.format.is_some() || cli.prometheus.is_some() || cli.bar.is_some()
+}
+
+/// AUR helper selected by --aur or one of its aliases
+fn selected_aur_helper<'a>(cli: &Cli, config: &'a Config) -> Option<&'a str> {
+    if cli.yay {
+        Some("yay")
+    } else if cli.paru {
+        Some("paru")
+    } else if cli.aur {
+        Some(config.aur.helper.as_str())
+    } else {
+        None
+    }
+}
+
+/// Merge `default_args` from the config into a bare invocation
+fn with_default_args(cli: Cli, config: &Config) -> Cli {
+    if !is_bare_invocation(&cli) || config.default_args.is_empty() {
+        return cli;
+    }
+    // Helper args after `--` in default_args go last, after the CLI's flags
+    let mut defaults = config.default_args.split_whitespace().map(String::from);
+    let mut args = vec!["pacfetch".to_string()];
+    args.extend(defaults.by_ref().take_while(|arg| arg != "--"));
+    let mut helper_args: Vec<String> = defaults.collect();
+    helper_args.extend(cli.helper_args.iter().cloned());
+    if cli.debug {
+        args.push("-d".to_string());
+    }
+    if let Some(ref ascii) = cli.ascii {
+        args.push("--ascii".to_string());
+        args.push(ascii.clone());
+    }
+    if let Some(ref color) = cli.color {
+        args.push("--color".to_string());
+        args.push(color.clone());
+    }
+    if let Some(ref image) = cli.image {
+        args.push("--image".to_string());
+        args.push(image.clone());
+    }
+    if cli.json {
+        args.push("--json".to_string());
+    }
+    if let Some(format) = cli.format
+        && let Some(value) = format.to_possible_value()
+    {
+        args.push("--format".to_string());
+        args.push(value.get_name().to_string());
+    }
+    if let Some(ref path) = cli.prometheus {
+        args.push("--prometheus".to_string());
+        args.push(path.display().to_string());
+    }
+    if let Some(bar) = cli.bar
+        && let Some(value) = bar.to_possible_value()
+    {
+        args.push("--bar".to_string());
+        args.push(value.get_name().to_string());
+    }
+    if let Some(ms) = cli.timeout {
+        args.push("--timeout".to_string());
+        args.push(ms.to_string());
+    }
+    if !helper_args.is_empty() {
+        args.push("--".to_string());
+        args.extend(helper_args);
+    }
+    match Cli::try_parse_from(&args) {
+        // Machine outputs keep options like --local but never pick up an
+        // upgrade or sync operation
+        Ok(merged) if is_machine_output(&merged) => Cli {
+            sync_op: false,
+            sync_db: false,
+            upgrade: false,
+            yay: false,
+            paru: false,
+            aur: false,
+            helper_args: Vec::new(),
+            ..merged
+        },
+        Ok(cli) => cli,
+        Err(_) => {
+            eprintln!(
+                "warning: invalid default_args in config: {:?}",
+                config.default_args
+            );
+            cli
+        }
+    }
 }
 
 fn print_error_and_help(msg: &str) -> ! {
@@ -133,40 +257,11 @@ fn main() {
         config.display.image = image.clone();
     }
 
-    let cli = if is_bare_invocation(&cli) && !config.default_args.is_empty() {
-        let mut args = vec!["pacfetch".to_string()];
-        args.extend(config.default_args.split_whitespace().map(String::from));
-        if cli.debug {
-            args.push("-d".to_string());
-        }
-        if let Some(ref ascii) = cli.ascii {
-            args.push("--ascii".to_string());
-            args.push(ascii.clone());
-        }
-        if let Some(ref color) = cli.color {
-            args.push("--color".to_string());
-            args.push(color.clone());
-        }
-        if let Some(ref image) = cli.image {
-            args.push("--image".to_string());
-            args.push(image.clone());
-        }
-        if cli.json {
-            args.push("--json".to_string());
-        }
-        match Cli::try_parse_from(&args) {
-            Ok(cli) => cli,
-            Err(_) => {
-                eprintln!(
-                    "warning: invalid default_args in config: {:?}",
-                    config.default_args
-                );
-                cli
-            }
-        }
-    } else {
-        cli
-    };
+    let cli = with_default_args(cli, &config);
+
+    if let Some(ms) = cli.timeout {
+        config.performance.budget_ms = ms;
+    }
 
     let invalid_flag = (cli.sync_op && !cli.sync_db && !cli.upgrade)
         || ((cli.sync_db || cli.upgrade) && !cli.sync_op);
@@ -184,124 +279,136 @@ fn main() {
         std::process::exit(0);
     }
 
-    // Handle --yay (full system + AUR upgrade via yay)
-    if cli.yay {
-        if let Err(e) = pacman::yay_upgrade(cli.debug, &config) {
+    // Handle --aur / --yay / --paru (full system + AUR upgrade via a helper)
+    if let Some(helper) = selected_aur_helper(&cli, &config) {
+        if let Err(e) = pacman::aur_helper_upgrade(helper, &cli.helper_args, cli.debug, &config) {
             eprintln!("error: {}", e);
             std::process::exit(1);
         }
         std::process::exit(0);
     }
 
-    // Handle --paru (full system + AUR upgrade via paru)
-    if cli.paru {
-        if let Err(e) = pacman::paru_upgrade(cli.debug, &config) {
-            eprintln!("error: {}", e);
-            std::process::exit(1);
-        }
-        std::process::exit(0);
+    if !cli.helper_args.is_empty() {
+        print_error_and_help("arguments after -- require --aur, --yay or --paru");
     }
 
-    // Skip fresh sync if: --local flag, or after -Sy
-    let fresh_sync = !(cli.local || cli.sync_op && cli.sync_db);
+    // Skip fresh sync if: --local flag, or after -Sy. A status bar polls
+    // often, so it only reuses temp databases still within the cache TTL.
+    let fresh_sync = !(cli.local || cli.sync_op && cli.sync_db)
+        && (cli.bar.is_none() || pacman::temp_dbs_fresh(config.cache.ttl_minutes));
+
+    let output_format = cli.format.or(cli.json.then_some(OutputFormat::Json));
+    let requested = if cli.prometheus.is_some() {
+        output::prometheus_stats()
+    } else if cli.bar.is_some() {
+        output::bar_stats(&config)
+    } else {
+        config.display.parsed_stats()
+    };
 
     // Get stats
     let stats = if cli.sync_op && cli.sync_db {
-        if let Err(e) = pacman::sync_databases() {
+        if let Err(e) = pacman::sync_databases(cli.debug) {
             eprintln!("error: {}", e);
             std::process::exit(1);
         }
         let spinner = util::create_spinner("Gathering stats");
-        let stats = pacman::get_stats(
-            &config.display.parsed_stats(),
-            cli.debug,
-            fresh_sync,
-            &config,
-            Some(&spinner),
-        );
+        let stats = pacman::get_stats(&requested, cli.debug, fresh_sync, &config, Some(&spinner));
         spinner.finish_and_clear();
         stats
-    } else if cli.json {
-        pacman::get_stats(
-            &config.display.parsed_stats(),
-            false,
-            fresh_sync,
-            &config,
-            None,
-        )
+    } else if output_format.is_some() || cli.prometheus.is_some() || cli.bar.is_some() {
+        pacman::get_stats(&requested, false, fresh_sync, &config, None)
     } else if cli.debug {
         println!();
-        pacman::get_stats(
-            &config.display.parsed_stats(),
-            cli.debug,
-            fresh_sync,
-            &config,
-            None,
-        )
+        pacman::get_stats(&requested, cli.debug, fresh_sync, &config, None)
     } else {
         let spinner = util::create_spinner("Gathering stats");
-        let stats = pacman::get_stats(
-            &config.display.parsed_stats(),
-            cli.debug,
-            fresh_sync,
-            &config,
-            Some(&spinner),
-        );
+        let stats = pacman::get_stats(&requested, cli.debug, fresh_sync, &config, Some(&spinner));
         spinner.finish_and_clear();
         stats
     };
 
-    if cli.json {
-        println!("{}", stats_to_json_string(&stats));
+    if let Some(ref path) = cli.prometheus {
+        if let Err(e) = output::write_prometheus(path, &stats) {
+            eprintln!("error: {}: {}", path.display(), e);
+            pacman::finish_temp_sync();
+            std::process::exit(1);
+        }
+    } else if let Some(bar) = cli.bar {
+        print!("{}", output::render_bar(bar, &stats, &config));
+    } else if let Some(format) = output_format {
+        match output::render(format, &stats, &config) {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                pacman::finish_temp_sync();
+                std::process::exit(1);
+            }
+        }
     } else if cli.debug {
         ui::display_stats(&stats, &config);
         println!();
     } else if let Err(e) = ui::display_stats_with_graphics(&stats, &config) {
         eprintln!("error: {}", e);
     }
-}
 
-fn stats_to_json_string(stats: &pacman::PacmanStats) -> String {
-    let mut map = serde_json::Map::new();
-    for id in stats::ALL_STAT_IDS {
-        if let Some(value) = id.format_value(stats) {
-            map.insert(
-                id.config_key().to_string(),
-                serde_json::Value::String(value),
-            );
-        }
-    }
-    serde_json::to_string_pretty(&serde_json::Value::Object(map))
-        .unwrap_or_else(|_| "{}".to_string())
+    // A temp sync past the budget still holds the cache lock
+    pacman::finish_temp_sync();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::pacman::PacmanStats;
 
     #[test]
-    fn test_json_contains_installed_and_upgradable() {
-        let stats = PacmanStats {
-            total_installed: 1234,
-            total_upgradable: 5,
-            ..Default::default()
-        };
-        let output = stats_to_json_string(&stats);
-        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
-        assert_eq!(parsed["installed"], "1234");
-        assert_eq!(parsed["upgradable"], "5");
+    fn test_aur_helper_pass_through_args() {
+        let cli = Cli::try_parse_from(["pacfetch", "--aur", "--", "--devel", "--needed"]).unwrap();
+        assert!(cli.aur);
+        assert_eq!(cli.helper_args, vec!["--devel", "--needed"]);
+        assert_eq!(selected_aur_helper(&cli, &Config::default()), Some("yay"));
+
+        let cli = Cli::try_parse_from(["pacfetch", "--paru"]).unwrap();
+        assert_eq!(selected_aur_helper(&cli, &Config::default()), Some("paru"));
+    }
+
+    #[test]
+    fn test_json_conflicts_with_format() {
+        assert!(Cli::try_parse_from(["pacfetch", "--json", "--format", "yaml"]).is_err());
     }
 
     #[test]
-    fn test_json_omits_none_values() {
-        let stats = PacmanStats {
-            total_installed: 100,
-            total_upgradable: 0,
-            ..Default::default()
+    fn test_machine_outputs_drop_default_operations() {
+        let config = Config {
+            default_args: "--yay".to_string(),
+            ..Config::default()
         };
-        let output = stats_to_json_string(&stats);
-        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
-        assert!(parsed.get("download_size").is_none());
+        let cli = with_default_args(
+            Cli::try_parse_from(["pacfetch", "--bar", "waybar"]).unwrap(),
+            &config,
+        );
+        assert_eq!(cli.bar, Some(BarFormat::Waybar));
+        assert_eq!(selected_aur_helper(&cli, &config), None);
+
+        let cli = with_default_args(
+            Cli::try_parse_from(["pacfetch", "--prometheus", "/tmp/p.prom"]).unwrap(),
+            &config,
+        );
+        assert_eq!(selected_aur_helper(&cli, &config), None);
+
+        let cli = with_default_args(Cli::try_parse_from(["pacfetch"]).unwrap(), &config);
+        assert_eq!(selected_aur_helper(&cli, &config), Some("yay"));
+
+        // Options in default_args still apply
+        let config = Config {
+            default_args: "-Syu --local -- --devel".to_string(),
+            ..Config::default()
+        };
+        let cli = with_default_args(
+            Cli::try_parse_from(["pacfetch", "--json"]).unwrap(),
+            &config,
+        );
+        assert!(cli.json && cli.local);
+        assert!(!cli.sync_op && !cli.sync_db && !cli.upgrade);
+        assert!(cli.helper_args.is_empty());
     }
 }
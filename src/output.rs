@@ -0,0 +1,849 @@
+use clap::ValueEnum;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::{Config, ThresholdLevel};
+use crate::pacman::{CustomOutput, PacmanStats};
+use crate::stats::{ALL_STAT_IDS, RenderContext, StatId, StatIdOrTitle};
+
+/// `--format` output for scripts and tickets
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Toml,
+    /// `PACFETCH_<STAT>=value` lines for `eval`
+    Env,
+    /// A table to paste into tickets
+    Markdown,
+}
+
+pub fn render(
+    format: OutputFormat,
+    stats: &PacmanStats,
+    config: &Config,
+) -> Result<String, String> {
+    let ctx = RenderContext::new(config);
+    match format {
+        OutputFormat::Json => Ok(stats_to_json_string(stats, &ctx)),
+        OutputFormat::Yaml => Ok(to_yaml(&json_document(stats, &ctx))),
+        OutputFormat::Toml => to_toml(json_document(stats, &ctx)),
+        OutputFormat::Env => Ok(to_env(stats, &ctx)),
+        OutputFormat::Markdown => Ok(to_markdown(stats, &ctx)),
+    }
+}
+
+pub fn stats_to_json_string(stats: &PacmanStats, ctx: &RenderContext) -> String {
+    serde_json::to_string_pretty(&json_document(stats, ctx)).unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Version of the `--json` document layout, bumped on breaking changes
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// The typed `--json` document
+fn json_document(stats: &PacmanStats, ctx: &RenderContext) -> serde_json::Value {
+    let mut values = serde_json::Map::new();
+    let mut unavailable = serde_json::Map::new();
+    for id in ALL_STAT_IDS {
+        if *id == StatId::Title {
+            continue;
+        }
+        let key = id.config_key().to_string();
+        match id.json_value(stats, ctx) {
+            Ok(value) => {
+                values.insert(key, value);
+                for (part, reason) in id.unavailable_parts(stats, ctx) {
+                    unavailable.insert(part, reason.into());
+                }
+            }
+            Err(reason) => {
+                values.insert(key.clone(), serde_json::Value::Null);
+                unavailable.insert(key, reason.into());
+            }
+        }
+    }
+
+    let mut custom = serde_json::Map::new();
+    for (name, output) in &stats.custom {
+        let value = match output {
+            CustomOutput::Value(value) => value.clone().into(),
+            CustomOutput::Failed(reason) => {
+                unavailable.insert(format!("custom.{}", name), reason.clone().into());
+                serde_json::Value::Null
+            }
+            CustomOutput::TimedOut => {
+                unavailable.insert(format!("custom.{}", name), "timed out".into());
+                serde_json::Value::Null
+            }
+        };
+        custom.insert(name.clone(), value);
+    }
+
+    serde_json::json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "generated_at": chrono::Local::now().to_rfc3339(),
+        "source": stats.source.as_ref().map(|source| serde_json::json!({
+            "database": source.database.as_str(),
+            "db_age_seconds": source.db_age_secs,
+        })),
+        "pacman_version": stats.pacman_version,
+        "stats": values,
+        "custom": custom,
+        "unavailable": unavailable,
+    })
+}
+
+/// Block-style YAML. Strings are double-quoted, which JSON and YAML share.
+fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    write_yaml(&mut out, value, 0);
+    out
+}
+
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, v) in map {
+                let key = if key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                {
+                    key.clone()
+                } else {
+                    yaml_scalar(&Value::String(key.clone()))
+                };
+                if is_block(v) {
+                    out.push_str(&format!("{}{}:\n", pad, key));
+                    write_yaml(out, v, indent + 2);
+                } else {
+                    out.push_str(&format!("{}{}: {}\n", pad, key, yaml_scalar(v)));
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                if is_block(item) {
+                    // Nest one level in, then put the dash over the first indent
+                    let mut nested = String::new();
+                    write_yaml(&mut nested, item, indent + 2);
+                    out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                } else {
+                    out.push_str(&format!("{}- {}\n", pad, yaml_scalar(item)));
+                }
+            }
+        }
+        scalar => out.push_str(&format!("{}{}\n", pad, yaml_scalar(scalar))),
+    }
+}
+
+/// A non-empty object or array, written as a block
+fn is_block(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// TOML has no null, so unavailable stats are only listed under `unavailable`
+fn to_toml(mut value: Value) -> Result<String, String> {
+    strip_nulls(&mut value);
+    toml::to_string(&value).map_err(|e| format!("Failed to encode TOML: {}", e))
+}
+
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => {
+            items.retain(|v| !v.is_null());
+            items.iter_mut().for_each(strip_nulls);
+        }
+        _ => {}
+    }
+}
+
+/// One `PACFETCH_<STAT>=value` line per stat. Numeric stats give their raw
+/// value (count, bytes, seconds or percent), the rest their display text.
+/// Unavailable stats are set empty.
+fn to_env(stats: &PacmanStats, ctx: &RenderContext) -> String {
+    let mut lines = Vec::new();
+    for id in ALL_STAT_IDS.iter().filter(|id| **id != StatId::Title) {
+        let value = match id.json_value(stats, ctx) {
+            Err(_) => String::new(),
+            Ok(_) => match id.metric(stats, ctx) {
+                Some(n) if n.fract() == 0.0 => format!("{}", n as i64),
+                Some(n) => format!("{:.1}", n),
+                None => shell_quote(&id.format_value(stats, ctx).unwrap_or_default()),
+            },
+        };
+        lines.push(format!("{}={}", env_name(id.config_key()), value));
+    }
+    for (name, output) in sorted_custom(stats) {
+        let value = match output {
+            CustomOutput::Value(value) => shell_quote(value),
+            _ => String::new(),
+        };
+        lines.push(format!(
+            "{}={}",
+            env_name(&format!("custom_{}", name)),
+            value
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// `PACFETCH_` plus the key upper-cased, with anything else as `_`
+fn env_name(key: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("PACFETCH_{}", key)
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// A two-column table of the display labels and values
+fn to_markdown(stats: &PacmanStats, ctx: &RenderContext) -> String {
+    let mut rows = vec![
+        "| Stat | Value |".to_string(),
+        "|------|-------|".to_string(),
+    ];
+    for id in ALL_STAT_IDS.iter().filter(|id| **id != StatId::Title) {
+        let value = match id.json_value(stats, ctx) {
+            Ok(_) => id.format_value(stats, ctx).unwrap_or_default(),
+            Err(reason) => format!("_{}_", reason),
+        };
+        rows.push(format!(
+            "| {} | {} |",
+            markdown_escape(&ctx.locale.label(*id)),
+            markdown_escape(&value)
+        ));
+    }
+    for (name, output) in sorted_custom(stats) {
+        let value = match output {
+            CustomOutput::Value(value) => value.clone(),
+            CustomOutput::Failed(reason) => format!("_{}_", reason),
+            CustomOutput::TimedOut => "_timed out_".to_string(),
+        };
+        rows.push(format!(
+            "| {} | {} |",
+            markdown_escape(name),
+            markdown_escape(&value)
+        ));
+    }
+    rows.join("\n") + "\n"
+}
+
+fn markdown_escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn sorted_custom(stats: &PacmanStats) -> Vec<(&String, &CustomOutput)> {
+    let mut custom: Vec<_> = stats.custom.iter().collect();
+    custom.sort_by_key(|(name, _)| *name);
+    custom
+}
+
+/// A gauge in the `--prometheus` textfile
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    stat: StatId,
+    value: fn(&PacmanStats) -> Option<f64>,
+}
+
+const METRICS: &[Metric] = &[
+    Metric {
+        name: "pacfetch_installed_packages",
+        help: "Installed packages",
+        stat: StatId::Installed,
+        value: |s| s.total_installed.map(|n| n as f64),
+    },
+    Metric {
+        name: "pacfetch_upgradable",
+        help: "Packages with a pending repo upgrade",
+        stat: StatId::Upgradable,
+        value: |s| {
+            s.total_upgradable
+                .filter(|_| s.invalid_repos.is_empty())
+                .map(|n| n as f64)
+        },
+    },
+    Metric {
+        name: "pacfetch_download_bytes",
+        help: "Bytes to download for the pending upgrade",
+        stat: StatId::DownloadSize,
+        value: |s| s.download_size_bytes.map(|b| b as f64),
+    },
+    Metric {
+        name: "pacfetch_net_upgrade_bytes",
+        help: "Change in installed size after the pending upgrade",
+        stat: StatId::NetUpgradeSize,
+        value: |s| s.net_upgrade_size_bytes.map(|b| b as f64),
+    },
+    Metric {
+        name: "pacfetch_seconds_since_update",
+        help: "Seconds since the last full system upgrade",
+        stat: StatId::LastUpdate,
+        value: |s| s.days_since_last_update.map(|secs| secs as f64),
+    },
+    Metric {
+        name: "pacfetch_orphans",
+        help: "Orphaned packages",
+        stat: StatId::OrphanedPackages,
+        value: |s| s.orphaned_packages.map(|n| n as f64),
+    },
+    Metric {
+        name: "pacfetch_orphans_bytes",
+        help: "Installed size of orphaned packages",
+        stat: StatId::OrphanedPackages,
+        value: |s| s.orphaned_size_bytes.map(|b| b as f64),
+    },
+    Metric {
+        name: "pacfetch_cache_bytes",
+        help: "Size of the package cache",
+        stat: StatId::CacheSize,
+        value: |s| s.cache_size_bytes.map(|b| b as f64),
+    },
+    Metric {
+        name: "pacfetch_mirror_sync_age_seconds",
+        help: "Seconds since the mirror last synced",
+        stat: StatId::MirrorHealth,
+        value: |s| s.mirror_sync_age_hours.map(|h| (h * 3600.0).round()),
+    },
+    Metric {
+        name: "pacfetch_disk_used_ratio",
+        help: "Used fraction of the filesystem holding the [disk] path",
+        stat: StatId::Disk,
+        value: |s| match (s.disk_used_bytes, s.disk_total_bytes) {
+            (Some(used), Some(total)) if total > 0 => Some(used as f64 / total as f64),
+            _ => None,
+        },
+    },
+];
+
+/// Stats the `--prometheus` metrics are read from
+pub fn prometheus_stats() -> Vec<StatIdOrTitle> {
+    let mut requested: Vec<StatIdOrTitle> = Vec::new();
+    for metric in METRICS {
+        let stat = StatIdOrTitle::Stat(metric.stat);
+        if !requested.contains(&stat) {
+            requested.push(stat);
+        }
+    }
+    requested
+}
+
+/// Metrics in the Prometheus text format. Stats without a value, or that
+/// timed out, are left out rather than reported as 0.
+fn prometheus_text(stats: &PacmanStats) -> String {
+    let mut out = String::new();
+    for metric in METRICS {
+        if stats.timed_out.contains(&metric.stat) {
+            continue;
+        }
+        let Some(value) = (metric.value)(stats) else {
+            continue;
+        };
+        out.push_str(&format!("# HELP {} {}\n", metric.name, metric.help));
+        out.push_str(&format!("# TYPE {} gauge\n", metric.name));
+        out.push_str(&format!("{} {}\n", metric.name, value));
+    }
+    out
+}
+
+/// Write the metrics for node_exporter's textfile collector. The file is
+/// written beside the target and renamed over it, so a scrape never sees
+/// a partial file.
+pub fn write_prometheus(path: &Path, stats: &PacmanStats) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    fs::write(&tmp, prometheus_text(stats))?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// `--bar` status bar module output
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum BarFormat {
+    Waybar,
+    I3blocks,
+    Polybar,
+}
+
+/// Stats that go over the network on every run, outside the temp db cache
+const BAR_NETWORK_STATS: &[StatId] = &[
+    StatId::AurUpgradable,
+    StatId::FlatpakUpgradable,
+    StatId::VulnerablePackages,
+    StatId::MirrorHealth,
+];
+
+/// Stats for `--bar`: the configured ones for the tooltip, plus the
+/// upgradable and installed counts the module shows. A bar polls often, so
+/// network-bound stats and custom commands are left out.
+pub fn bar_stats(config: &Config) -> Vec<StatIdOrTitle> {
+    let mut requested: Vec<StatIdOrTitle> = config
+        .display
+        .parsed_stats()
+        .into_iter()
+        .filter(|item| match item {
+            StatIdOrTitle::Stat(id) => !BAR_NETWORK_STATS.contains(id),
+            StatIdOrTitle::Custom(_) => false,
+            _ => true,
+        })
+        .collect();
+    for id in [StatId::Upgradable, StatId::Installed] {
+        if !requested.contains(&StatIdOrTitle::Stat(id)) {
+            requested.push(StatIdOrTitle::Stat(id));
+        }
+    }
+    requested
+}
+
+/// The upgradable count as a status bar module. `[display.thresholds.upgradable]`
+/// picks the class and color; an unavailable count is critical.
+pub fn render_bar(format: BarFormat, stats: &PacmanStats, config: &Config) -> String {
+    let ctx = RenderContext::new(config);
+    let count = StatId::Upgradable.metric(stats, &ctx);
+    let text = count.map_or("?".to_string(), |n| format!("{}", n as i64));
+
+    let thresholds = config.display.parsed_thresholds();
+    let rule = thresholds.get(&StatId::Upgradable);
+    let level = match (count, rule) {
+        (None, _) => ThresholdLevel::Critical,
+        (Some(n), Some(rule)) => rule.level(n),
+        (Some(_), None) => ThresholdLevel::Ok,
+    };
+    let color = rule
+        .map(|rule| match level {
+            ThresholdLevel::Ok => rule.ok_color.as_str(),
+            ThresholdLevel::Warn => rule.warn_color.as_str(),
+            ThresholdLevel::Critical => rule.critical_color.as_str(),
+        })
+        .and_then(crate::color::parse_color)
+        .and_then(crate::color::to_hex);
+
+    match format {
+        BarFormat::Waybar => {
+            let tooltip: Vec<String> =
+                crate::ui::render_stats(stats, &ctx, config, &bar_stats(config))
+                    .iter()
+                    .map(|line| crate::util::strip_ansi(line))
+                    .collect();
+            // Share of installed packages with a pending upgrade
+            let percentage = match (count, stats.total_installed) {
+                (Some(n), Some(total)) if total > 0 => {
+                    (n / total as f64 * 100.0).round().min(100.0) as u32
+                }
+                _ => 0,
+            };
+            serde_json::json!({
+                "text": text,
+                "tooltip": tooltip.join("\n").trim_end(),
+                "class": level.as_str(),
+                "percentage": percentage,
+            })
+            .to_string()
+                + "\n"
+        }
+        // full_text, short_text, then color
+        BarFormat::I3blocks => match color {
+            Some(hex) => format!("{}\n{}\n{}\n", text, text, hex),
+            None => format!("{}\n{}\n", text, text),
+        },
+        BarFormat::Polybar => match color {
+            Some(hex) => format!("%{{F{}}}{}%{{F-}}\n", hex, text),
+            None => format!("{}\n", text),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pacman;
+
+    fn ctx() -> RenderContext {
+        RenderContext::new(&Config::default())
+    }
+
+    /// The `--json` document as a script reads it
+    fn json(stats: &PacmanStats, ctx: &RenderContext) -> Value {
+        serde_json::from_str(&stats_to_json_string(stats, ctx)).unwrap()
+    }
+
+    #[test]
+    fn test_json_contains_installed_and_upgradable() {
+        let stats = PacmanStats {
+            total_installed: Some(1234),
+            total_upgradable: Some(5),
+            ..Default::default()
+        };
+        let parsed = json(&stats, &ctx());
+        assert_eq!(parsed["schema_version"], JSON_SCHEMA_VERSION);
+        assert!(parsed["source"].is_null());
+        assert_eq!(parsed["stats"]["installed"], 1234);
+        assert_eq!(parsed["stats"]["upgradable"]["total"], 5);
+    }
+
+    #[test]
+    fn test_json_failed_upgrade_check_is_unavailable() {
+        let stats = PacmanStats {
+            total_installed: Some(1234),
+            collected: vec![StatId::Installed, StatId::Upgradable],
+            ..Default::default()
+        };
+        let parsed = json(&stats, &ctx());
+        assert!(parsed["stats"]["upgradable"].is_null());
+        assert_eq!(parsed["unavailable"]["upgradable"], "failed");
+        assert_eq!(parsed["unavailable"]["mirror_url"], "not collected");
+    }
+
+    #[test]
+    fn test_json_installed_breakdown() {
+        let stats = PacmanStats {
+            total_installed: Some(1234),
+            explicit_installed: Some(312),
+            dependency_installed: Some(922),
+            ..Default::default()
+        };
+        let parsed = json(&stats, &ctx());
+        assert_eq!(parsed["stats"]["installed"], 1234);
+        assert_eq!(parsed["stats"]["explicit_installed"], 312);
+        assert_eq!(parsed["stats"]["dependency_installed"], 922);
+    }
+
+    #[test]
+    fn test_json_upgradable_aur_breakdown() {
+        let stats = PacmanStats {
+            total_upgradable: Some(37),
+            aur_upgradable: Some(pacman::AurStatus::Upgradable(5)),
+            ..Default::default()
+        };
+        let parsed = json(&stats, &ctx());
+        assert_eq!(parsed["stats"]["upgradable"]["total"], 42);
+        assert_eq!(parsed["stats"]["upgradable"]["aur"], 5);
+        assert_eq!(parsed["stats"]["aur_upgradable"], 5);
+
+        let stats = PacmanStats {
+            flatpak_upgradable: Some(pacman::FlatpakUpdates {
+                system: vec!["org.gimp.GIMP".to_string()],
+                user: Vec::new(),
+            }),
+            ..stats
+        };
+        let ctx = RenderContext {
+            flatpak_in_upgradable: true,
+            ..ctx()
+        };
+        let parsed = json(&stats, &ctx);
+        assert_eq!(parsed["stats"]["upgradable"]["total"], 43);
+        assert_eq!(
+            parsed["stats"]["flatpak_upgradable"]["system"][0],
+            "org.gimp.GIMP"
+        );
+    }
+
+    #[test]
+    fn test_json_aur_unreachable_is_not_zero() {
+        let stats = PacmanStats {
+            total_upgradable: Some(3),
+            aur_upgradable: Some(pacman::AurStatus::Unreachable),
+            ..Default::default()
+        };
+        let parsed = json(&stats, &ctx());
+        assert_eq!(parsed["stats"]["upgradable"]["total"], 3);
+        assert!(parsed["stats"]["upgradable"]["aur"].is_null());
+        assert_eq!(parsed["unavailable"]["upgradable.aur"], "AUR unreachable");
+        assert!(parsed["stats"]["aur_upgradable"].is_null());
+        assert_eq!(parsed["unavailable"]["aur_upgradable"], "AUR unreachable");
+
+        let stats = PacmanStats {
+            aur_upgradable: Some(pacman::AurStatus::InvalidSignature),
+            ..stats
+        };
+        let parsed = json(&stats, &ctx());
+        assert_eq!(parsed["unavailable"]["aur_upgradable"], "invalid signature");
+        assert_eq!(
+            StatId::AurUpgradable
+                .format_value(&stats, &ctx())
+                .as_deref(),
+            Some("Err - invalid signature")
+        );
+    }
+
+    #[test]
+    fn test_json_largest_packages_is_array() {
+        let stats = PacmanStats {
+            largest_packages: Some(vec![pacman::PackageSize {
+                name: "linux-firmware".to_string(),
+                size_bytes: 536870912,
+                exclusive_deps: 0,
+            }]),
+            ..Default::default()
+        };
+        let parsed = json(&stats, &ctx());
+        assert_eq!(
+            parsed["stats"]["largest_packages"][0]["name"],
+            "linux-firmware"
+        );
+        assert_eq!(
+            parsed["stats"]["largest_packages"][0]["size_bytes"],
+            536870912
+        );
+    }
+
+    #[test]
+    fn test_json_custom_stats() {
+        let mut stats = PacmanStats::default();
+        stats.custom.insert(
+            "backup".to_string(),
+            pacman::CustomOutput::Value("3 hours ago".to_string()),
+        );
+        stats
+            .custom
+            .insert("vpn".to_string(), pacman::CustomOutput::TimedOut);
+        let parsed = json(&stats, &ctx());
+        assert_eq!(parsed["custom"]["backup"], "3 hours ago");
+        assert!(parsed["custom"]["vpn"].is_null());
+        assert_eq!(parsed["unavailable"]["custom.vpn"], "timed out");
+    }
+
+    #[test]
+    fn test_json_missing_values_are_null_with_reason() {
+        let stats = PacmanStats {
+            total_installed: Some(100),
+            total_upgradable: Some(0),
+            timed_out: vec![StatId::CacheSize],
+            ..Default::default()
+        };
+        let parsed = json(&stats, &ctx());
+        assert!(parsed["stats"]["download_size"].is_null());
+        assert_eq!(parsed["unavailable"]["download_size"], "not collected");
+        assert_eq!(parsed["unavailable"]["cache_size"], "timed out");
+        assert!(parsed["unavailable"].get("installed").is_none());
+    }
+
+    #[test]
+    fn test_output_formats_cover_every_stat() {
+        let mut stats = PacmanStats {
+            total_installed: Some(1234),
+            total_upgradable: Some(5),
+            mirror_url: Some("https://mirror.example/$repo".to_string()),
+            largest_packages: Some(vec![pacman::PackageSize {
+                name: "linux-firmware".to_string(),
+                size_bytes: 536870912,
+                exclusive_deps: 0,
+            }]),
+            ..Default::default()
+        };
+        stats.custom.insert(
+            "backup".to_string(),
+            pacman::CustomOutput::Value("it's done".to_string()),
+        );
+
+        let env = render(OutputFormat::Env, &stats, &Config::default()).unwrap();
+        assert!(env.contains("PACFETCH_UPGRADABLE=5\n"));
+        assert!(env.contains("PACFETCH_MIRROR_URL='https://mirror.example/$repo'\n"));
+        assert!(env.contains("PACFETCH_DOWNLOAD_SIZE=\n"));
+        assert!(env.contains("PACFETCH_CUSTOM_BACKUP='it'\\''s done'\n"));
+
+        let markdown = render(OutputFormat::Markdown, &stats, &Config::default()).unwrap();
+        assert!(markdown.starts_with("| Stat | Value |\n"));
+        assert!(markdown.contains("| Installed | 1234 |"));
+        assert!(markdown.contains("| Download Size | _not collected_ |"));
+
+        let yaml = render(OutputFormat::Yaml, &stats, &Config::default()).unwrap();
+        assert!(yaml.contains("schema_version: 1\n"));
+        assert!(yaml.contains(
+            "  largest_packages:\n    - exclusive_deps: 0\n      name: \"linux-firmware\"\n"
+        ));
+
+        let toml: toml::Value =
+            toml::from_str(&render(OutputFormat::Toml, &stats, &Config::default()).unwrap())
+                .unwrap();
+        assert_eq!(toml["stats"]["installed"].as_integer(), Some(1234));
+        assert!(toml["stats"].get("download_size").is_none());
+        assert_eq!(
+            toml["unavailable"]["download_size"].as_str(),
+            Some("not collected")
+        );
+        for id in ALL_STAT_IDS.iter().filter(|id| **id != StatId::Title) {
+            assert!(env.contains(&format!("PACFETCH_{}=", id.config_key().to_uppercase())));
+        }
+    }
+
+    #[test]
+    fn test_yaml_quotes_strings_and_keys() {
+        let value = serde_json::json!({
+            "custom": { "note: odd": "say \"hi\"\nbye" },
+            "empty": {},
+            "list": [{ "name": "a", "size": 1 }, "b"],
+            "none": null,
+        });
+        let expected = r#"custom:
+  "note: odd": "say \"hi\"\nbye"
+empty: {}
+list:
+  - name: "a"
+    size: 1
+  - "b"
+none: null
+"#;
+        assert_eq!(to_yaml(&value), expected);
+    }
+
+    #[test]
+    fn test_toml_strips_nulls() {
+        let value = serde_json::json!({
+            "stats": { "installed": 5, "download_size": null },
+            "items": [1, null, { "name": "a", "size": null }],
+        });
+        let parsed: toml::Value = toml::from_str(&to_toml(value).unwrap()).unwrap();
+        assert_eq!(parsed["stats"]["installed"].as_integer(), Some(5));
+        assert!(parsed["stats"].get("download_size").is_none());
+        let items = parsed["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(items[1].get("size").is_none());
+    }
+
+    #[test]
+    fn test_prometheus_textfile() {
+        let stats = PacmanStats {
+            total_upgradable: Some(5),
+            download_size_bytes: Some(1048576),
+            orphaned_packages: Some(2),
+            disk_used_bytes: Some(25),
+            disk_total_bytes: Some(100),
+            timed_out: vec![StatId::OrphanedPackages],
+            ..Default::default()
+        };
+        let dir = std::env::temp_dir().join(format!("pacfetch-prom-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pacfetch.prom");
+        write_prometheus(&path, &stats).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("# TYPE pacfetch_upgradable gauge\npacfetch_upgradable 5\n"));
+        assert!(text.contains("pacfetch_download_bytes 1048576\n"));
+        assert!(text.contains("pacfetch_disk_used_ratio 0.25\n"));
+        // Timed out and missing stats are left out, not reported as 0
+        assert!(!text.contains("pacfetch_orphans"));
+        assert!(!text.contains("pacfetch_seconds_since_update"));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_bar_output() {
+        let config: Config = toml::from_str(
+            r#"
+            [display]
+            stats = ["installed", "upgradable"]
+
+            [display.thresholds.upgradable]
+            warn = "10"
+            critical = "50"
+            "#,
+        )
+        .unwrap();
+        let stats = PacmanStats {
+            total_installed: Some(200),
+            explicit_installed: Some(80),
+            total_upgradable: Some(12),
+            ..Default::default()
+        };
+
+        let waybar = render_bar(BarFormat::Waybar, &stats, &config);
+        let parsed: serde_json::Value = serde_json::from_str(&waybar).unwrap();
+        assert_eq!(parsed["text"], "12");
+        assert_eq!(parsed["class"], "warn");
+        assert_eq!(parsed["percentage"], 6);
+        let tooltip = parsed["tooltip"].as_str().unwrap();
+        assert!(tooltip.contains("Upgradable"));
+        assert!(!tooltip.contains('\x1b'));
+
+        assert_eq!(
+            render_bar(BarFormat::I3blocks, &stats, &config),
+            "12\n12\n#ffff00\n"
+        );
+        assert_eq!(
+            render_bar(BarFormat::Polybar, &stats, &config),
+            "%{F#ffff00}12%{F-}\n"
+        );
+
+        // A failed upgrade check is unknown, not 0
+        let failed = PacmanStats {
+            total_upgradable: None,
+            collected: vec![StatId::Upgradable],
+            ..Default::default()
+        };
+        assert_eq!(
+            render_bar(BarFormat::I3blocks, &failed, &config),
+            "?\n?\n#ff0000\n"
+        );
+
+        let stats = PacmanStats {
+            invalid_repos: vec!["core".to_string()],
+            ..stats
+        };
+        let waybar = render_bar(BarFormat::Waybar, &stats, &config);
+        let parsed: serde_json::Value = serde_json::from_str(&waybar).unwrap();
+        assert_eq!(parsed["text"], "?");
+        assert_eq!(parsed["class"], "critical");
+    }
+
+    #[test]
+    fn test_bar_skips_network_stats() {
+        let config: Config = toml::from_str(
+            r#"
+            [display]
+            stats = ["orphaned_packages", "mirror_health", "aur_upgradable", "custom.backup"]
+
+            [display.custom.backup]
+            command = "true"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            bar_stats(&config),
+            vec![
+                StatIdOrTitle::Stat(StatId::OrphanedPackages),
+                StatIdOrTitle::Stat(StatId::Upgradable),
+                StatIdOrTitle::Stat(StatId::Installed),
+            ]
+        );
+    }
+}
@@ -1,33 +1,69 @@
 use crate::stats::{
-    StatId, StatIdOrTitle, needs_disk_stat, needs_mirror_health, needs_mirror_url,
-    needs_orphan_stats, needs_upgrade_stats,
+    StatId, StatIdOrTitle, needs_aur_stats, needs_db_lock_stat, needs_disk_stat,
+    needs_flatpak_stats, needs_installed_stats, needs_keyring_stat, needs_largest_packages,
+    needs_mirror_health, needs_mirror_url, needs_orphan_stats, needs_recent_packages,
+    needs_security_stats, needs_upgrade_stats, requested_custom_stats,
 };
 use crate::util;
 use alpm::Alpm;
 use chrono::{DateTime, FixedOffset, Local};
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::ProgressBar;
 use raur::Raur as _;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::os::unix::fs::symlink;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, OnceLock, mpsc};
 use std::time::Instant;
 
 const BYTES_PER_MIB: f64 = 1048576.0;
+const KEYRING_PACKAGE: &str = "archlinux-keyring";
+const DB_LOCK_PATH: &str = "/var/lib/pacman/db.lck";
+const PACMAN_LOG_PATH: &str = "/var/log/pacman.log";
+/// Bytes read per step when scanning pacman.log from the end
+const LOG_CHUNK_BYTES: u64 = 64 * 1024;
 
 // --- Public data structures ---
 
 #[derive(Debug, Default)]
 pub struct PacmanStats {
-    pub total_installed: u32,
-    pub total_upgradable: u32,
+    /// None if the local db couldn't be read
+    pub total_installed: Option<u32>,
+    pub explicit_installed: Option<u32>,
+    pub dependency_installed: Option<u32>,
+    /// None if the upgrade check failed
+    pub total_upgradable: Option<u32>,
+    pub aur_upgradable: Option<AurStatus>,
+    pub flatpak_upgradable: Option<FlatpakUpdates>,
+    pub vulnerable_packages: Option<VulnerablePackages>,
+    pub largest_packages: Option<Vec<PackageSize>>,
+    pub recent_packages: Option<Vec<RecentPackage>>,
+    pub keyring: Option<KeyringStatus>,
+    pub db_lock: Option<DbLockStatus>,
+    /// Sync dbs that failed their pacman.conf SigLevel check
+    pub invalid_repos: Vec<String>,
+    /// Stats whose collector didn't finish within `[performance] budget_ms`
+    pub timed_out: Vec<StatId>,
+    /// `[display.custom.<name>]` results by name
+    pub custom: HashMap<String, CustomOutput>,
+    /// Package databases the upgrade stats were computed from, None if the
+    /// upgrade check didn't run or failed
+    pub source: Option<DataSource>,
+    /// Stats whose collector finished, with or without a value
+    pub collected: Vec<StatId>,
+    pub last_update_time: Option<DateTime<Local>>,
     pub days_since_last_update: Option<i64>,
-    pub download_size_mb: Option<f64>,
-    pub total_installed_size_mb: Option<f64>,
-    pub net_upgrade_size_mb: Option<f64>,
+    pub download_size_bytes: Option<i64>,
+    pub installed_size_bytes: Option<i64>,
+    pub net_upgrade_size_bytes: Option<i64>,
     pub orphaned_packages: Option<u32>,
-    pub orphaned_size_mb: Option<f64>,
-    pub cache_size_mb: Option<f64>,
+    pub orphaned_size_bytes: Option<i64>,
+    pub cache_size_bytes: Option<u64>,
     pub mirror_url: Option<String>,
     pub mirror_sync_age_hours: Option<f64>,
     pub pacman_version: Option<String>,
@@ -35,91 +71,371 @@ pub struct PacmanStats {
     pub disk_total_bytes: Option<u64>,
 }
 
+/// Which sync databases the stats came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DbSource {
+    /// The pacfetch temp copy under ~/.cache/pacfetch, synced for this run
+    /// or reused within the cache TTL
+    Temp,
+    /// The system databases in /var/lib/pacman
+    Local,
+}
+
+impl DbSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DbSource::Temp => "temp",
+            DbSource::Local => "local",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataSource {
+    pub database: DbSource,
+    /// Seconds since the oldest sync db was last synced
+    pub db_age_secs: Option<u64>,
+}
+
+/// Result of a custom stat's command
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomOutput {
+    Value(String),
+    Failed(String),
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AurStatus {
+    Upgradable(u32),
+    Unreachable,
+    /// A sync db failed verification, so foreign packages can't be told apart
+    InvalidSignature,
+}
+
+impl AurStatus {
+    /// Why there's no count, for the error value and JSON `unavailable`
+    pub fn error(&self) -> Option<&'static str> {
+        match self {
+            AurStatus::Upgradable(_) => None,
+            AurStatus::Unreachable => Some("AUR unreachable"),
+            AurStatus::InvalidSignature => Some("invalid signature"),
+        }
+    }
+}
+
+/// Pending Flatpak updates by installation, as application IDs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlatpakUpdates {
+    pub system: Vec<String>,
+    pub user: Vec<String>,
+}
+
+impl FlatpakUpdates {
+    pub fn count(&self) -> u32 {
+        (self.system.len() + self.user.len()) as u32
+    }
+}
+
+/// An installed package affected by an open security advisory
+#[derive(Debug, Clone, PartialEq)]
+pub struct VulnerablePackage {
+    pub name: String,
+    pub installed_version: String,
+    pub severity: String,
+    pub fixed_version: Option<String>,
+    pub fixed_by_upgrade: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VulnerablePackages {
+    pub packages: Vec<VulnerablePackage>,
+}
+
+/// Tracker severities, most severe first
+pub const SEVERITIES: [&str; 5] = ["Critical", "High", "Medium", "Low", "Unknown"];
+
+impl VulnerablePackages {
+    pub fn count(&self) -> u32 {
+        self.packages.len() as u32
+    }
+
+    pub fn fixed_by_upgrade(&self) -> u32 {
+        self.packages.iter().filter(|p| p.fixed_by_upgrade).count() as u32
+    }
+
+    pub fn no_fix(&self) -> u32 {
+        self.packages
+            .iter()
+            .filter(|p| p.fixed_version.is_none())
+            .count() as u32
+    }
+
+    /// Fix released but not reachable through the pending upgrade
+    pub fn fix_pending(&self) -> u32 {
+        self.count() - self.fixed_by_upgrade() - self.no_fix()
+    }
+
+    /// Packages per severity, in SEVERITIES order
+    pub fn severity_counts(&self) -> Vec<(&'static str, u32)> {
+        SEVERITIES
+            .iter()
+            .map(|sev| {
+                let n = self.packages.iter().filter(|p| p.severity == *sev).count() as u32;
+                (*sev, n)
+            })
+            .collect()
+    }
+}
+
+/// An installed package and its size, including exclusive dependencies
+/// when `[largest_packages] exclusive_deps` is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageSize {
+    pub name: String,
+    pub size_bytes: i64,
+    pub exclusive_deps: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackageChange {
+    Installed,
+    Upgraded,
+    Downgraded,
+    Reinstalled,
+}
+
+impl PackageChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PackageChange::Installed => "installed",
+            PackageChange::Upgraded => "upgraded",
+            PackageChange::Downgraded => "downgraded",
+            PackageChange::Reinstalled => "reinstalled",
+        }
+    }
+}
+
+/// The latest pacman.log change of a still-installed package
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentPackage {
+    pub name: String,
+    pub version: String,
+    pub change: PackageChange,
+    pub seconds_ago: i64,
+}
+
+/// Installed archlinux-keyring against the synced databases
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyringStatus {
+    pub installed: String,
+    /// Newer version in the sync db, if any
+    pub available: Option<String>,
+}
+
+/// Compare the installed keyring with the version the upgrade would install
+fn keyring_status(installed: String, pending: &HashMap<String, String>) -> KeyringStatus {
+    let available = pending
+        .get(KEYRING_PACKAGE)
+        .filter(|new| alpm::vercmp(new.as_str(), installed.as_str()) == Ordering::Greater)
+        .cloned();
+    KeyringStatus {
+        installed,
+        available,
+    }
+}
+
+/// A process that may hold the pacman database lock
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbLockStatus {
+    Unlocked,
+    Held {
+        age_secs: i64,
+        holders: Vec<LockHolder>,
+    },
+    /// Lock file left behind with no pacman or libalpm process running
+    Stale {
+        age_secs: i64,
+    },
+}
+
 // --- Private helpers ---
 
 #[derive(Default)]
 struct UpgradeStats {
-    download_size_mb: Option<f64>,
-    installed_size_mb: Option<f64>,
-    net_upgrade_size_mb: Option<f64>,
-    package_count: u32,
+    download_size_bytes: Option<i64>,
+    installed_size_bytes: Option<i64>,
+    net_upgrade_size_bytes: Option<i64>,
+    /// None if the check failed
+    package_count: Option<u32>,
+    /// name -> new version for every package the upgrade would install
+    pending_versions: HashMap<String, String>,
+    /// Installed archlinux-keyring version
+    keyring_version: Option<String>,
+    invalid_repos: Vec<String>,
+    /// Mirror that served the temp sync
+    mirror_url: Option<String>,
+}
+
+/// A `[repo]` section of pacman.conf with its effective SigLevel and servers
+#[derive(Debug, PartialEq)]
+struct SyncRepo {
+    name: String,
+    siglevel: alpm::SigLevel,
+    servers: Vec<Mirror>,
+}
+
+/// A `Server` entry with `$repo`/`$arch` filled in
+#[derive(Debug, PartialEq)]
+struct Mirror {
+    url: String,
+    /// The part before `/$repo`, as shown by mirror_url
+    base: String,
+}
+
+impl Mirror {
+    fn new(server: &str, repo: &str, arch: &str) -> Self {
+        Mirror {
+            url: server.replace("$repo", repo).replace("$arch", arch),
+            base: server.split("/$repo").next().unwrap_or(server).to_string(),
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
 enum DbSyncState {
-    Syncing(u8),
+    Syncing { downloaded: i64, total: i64 },
     Complete,
+    Failed,
 }
 
+struct RepoSync {
+    name: String,
+    state: DbSyncState,
+    started: Instant,
+}
+
+/// Per-repo db download progress, fed by libalpm download events
 struct SyncProgress {
-    core: DbSyncState,
-    extra: DbSyncState,
-    multilib: DbSyncState,
+    repos: Vec<RepoSync>,
 }
 
 impl SyncProgress {
-    fn new() -> Self {
+    fn new(names: Vec<String>) -> Self {
         Self {
-            core: DbSyncState::Syncing(0),
-            extra: DbSyncState::Syncing(0),
-            multilib: DbSyncState::Syncing(0),
+            repos: names
+                .into_iter()
+                .map(|name| RepoSync {
+                    name,
+                    state: DbSyncState::Syncing {
+                        downloaded: 0,
+                        total: 0,
+                    },
+                    started: Instant::now(),
+                })
+                .collect(),
         }
     }
 
     fn format(&self) -> String {
-        format!(
-            "core {} | extra {} | multilib {}",
-            Self::format_state(self.core),
-            Self::format_state(self.extra),
-            Self::format_state(self.multilib)
-        )
-    }
-
-    fn format_state(state: DbSyncState) -> String {
-        match state {
-            DbSyncState::Syncing(pct) => format!("{}%", pct),
+        self.repos
+            .iter()
+            .map(|r| format!("{} {}", r.name, Self::format_state(r)))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    fn format_state(repo: &RepoSync) -> String {
+        match repo.state {
+            DbSyncState::Syncing { downloaded, total } => {
+                let pct = if total > 0 {
+                    (downloaded * 100 / total).clamp(0, 100)
+                } else {
+                    0
+                };
+                let secs = repo.started.elapsed().as_secs_f64();
+                if downloaded > 0 && secs > 0.0 {
+                    format!("{}% {}", pct, format_rate(downloaded as f64 / secs))
+                } else {
+                    format!("{}%", pct)
+                }
+            }
             DbSyncState::Complete => "✓".to_string(),
+            DbSyncState::Failed => "✗".to_string(),
         }
     }
 
-    fn update_from_line(&mut self, line: &str) {
-        let clean = util::strip_ansi(line);
-        let trimmed = clean.trim();
-
-        if trimmed.contains("is up to date") {
-            if trimmed.starts_with("core") {
-                self.core = DbSyncState::Complete;
-            } else if trimmed.starts_with("extra") {
-                self.extra = DbSyncState::Complete;
-            } else if trimmed.starts_with("multilib") {
-                self.multilib = DbSyncState::Complete;
-            }
+    /// Apply a libalpm download event for `filename`, e.g. `core.db`
+    fn update(&mut self, filename: &str, event: alpm::DownloadEvent, debug: bool) {
+        // Signature downloads only matter if they fail the whole update
+        let Some(name) = filename.strip_suffix(".db") else {
             return;
-        }
-
-        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let db_name = parts[0];
-            let last = parts[parts.len() - 1];
+        };
+        let Some(repo) = self.repos.iter_mut().find(|r| r.name == name) else {
+            return;
+        };
 
-            if let Some(pct_str) = last.strip_suffix('%')
-                && let Ok(pct) = pct_str.parse::<u8>()
-            {
-                let state = if pct >= 100 {
-                    DbSyncState::Complete
-                } else {
-                    DbSyncState::Syncing(pct)
+        match event {
+            alpm::DownloadEvent::Init(_) => {
+                repo.started = Instant::now();
+            }
+            alpm::DownloadEvent::Progress(p) => {
+                repo.state = DbSyncState::Syncing {
+                    downloaded: p.downloaded,
+                    total: p.total,
                 };
-
-                match db_name {
-                    "core" => self.core = state,
-                    "extra" => self.extra = state,
-                    "multilib" => self.multilib = state,
-                    _ => {}
+            }
+            alpm::DownloadEvent::Retry(_) => {}
+            alpm::DownloadEvent::Completed(c) => {
+                repo.state = match c.result {
+                    alpm::DownloadResult::Failed => DbSyncState::Failed,
+                    _ => DbSyncState::Complete,
+                };
+                if debug {
+                    eprintln!(
+                        "  {}: {:?}, {:.2} MiB in {:?}",
+                        filename,
+                        c.result,
+                        c.total.max(0) as f64 / BYTES_PER_MIB,
+                        repo.started.elapsed()
+                    );
                 }
             }
         }
     }
+
+    /// Start a repo over, for a retry against another mirror
+    fn restart(&mut self, name: &str) {
+        if let Some(repo) = self.repos.iter_mut().find(|r| r.name == name) {
+            repo.state = DbSyncState::Syncing {
+                downloaded: 0,
+                total: 0,
+            };
+            repo.started = Instant::now();
+        }
+    }
+
+    fn failed(&self) -> Vec<String> {
+        self.repos
+            .iter()
+            .filter(|r| matches!(r.state, DbSyncState::Failed))
+            .map(|r| r.name.clone())
+            .collect()
+    }
+}
+
+fn format_rate(bytes_per_sec: f64) -> String {
+    if bytes_per_sec >= BYTES_PER_MIB {
+        format!("{:.1} MiB/s", bytes_per_sec / BYTES_PER_MIB)
+    } else {
+        format!("{:.0} KiB/s", bytes_per_sec / 1024.0)
+    }
 }
 
 /// Copy modification time from src to dest using libc
@@ -180,6 +496,11 @@ impl DbCache {
         self.path.join("sync")
     }
 
+    /// Sync dir of the temp databases, without creating it
+    fn sync_dir_path() -> Option<PathBuf> {
+        Some(crate::config::Config::cache_dir()?.parent()?.join("sync"))
+    }
+
     fn is_fresh(&self, ttl_minutes: u32) -> bool {
         if ttl_minutes == 0 {
             return false;
@@ -222,8 +543,10 @@ impl DbCache {
         if let Ok(entries) = fs::read_dir(&source_sync) {
             for entry in entries.filter_map(|e| e.ok()) {
                 let path = entry.path();
-                if path.extension().is_some_and(|ext| ext == "db")
-                    && let Some(filename) = path.file_name()
+                // Signatures come along so the SigLevel check can verify the copies
+                if let Some(filename) = path.file_name()
+                    && let Some(name) = filename.to_str()
+                    && (name.ends_with(".db") || name.ends_with(".db.sig"))
                 {
                     let dest = sync_dir.join(filename);
                     // Only copy if dest doesn't exist or is older than source
@@ -242,6 +565,57 @@ impl DbCache {
         }
     }
 
+    /// File recording which mirror served each repo in the last sync
+    fn mirror_file(&self) -> PathBuf {
+        self.path.join("mirror")
+    }
+
+    /// Remember the mirror that served each repo in the last sync, one
+    /// `repo base` per line
+    fn set_served_mirrors(&self, served: &[(String, String)]) {
+        let lines: String = served
+            .iter()
+            .map(|(repo, base)| format!("{} {}\n", repo, base))
+            .collect();
+        let _ = fs::write(self.mirror_file(), lines);
+    }
+
+    /// The mirror that served the most repos in the last sync, for
+    /// mirror_url. Ties go to the first in pacman.conf order.
+    fn served_mirror(&self) -> Option<String> {
+        let contents = fs::read_to_string(self.mirror_file()).ok()?;
+        let bases: Vec<&str> = contents
+            .lines()
+            .filter_map(|line| line.split_once(' ').map(|(_, base)| base.trim()))
+            .filter(|base| !base.is_empty())
+            .collect();
+        let count = |base: &str| bases.iter().filter(|b| **b == base).count();
+        let mut best: Option<&str> = None;
+        for base in &bases {
+            if best.is_none_or(|b| count(base) > count(b)) {
+                best = Some(base);
+            }
+        }
+        best.map(String::from)
+    }
+
+    /// Remove a db.lck left in the cache dir by a sync that was killed,
+    /// which would fail every later sync
+    fn remove_stale_lock(&self, debug: bool) {
+        let lock = self.path.join("db.lck");
+        if lock.exists() && lock_file_holders(&lock).is_empty() {
+            if debug {
+                eprintln!("  Removing stale {}", lock.display());
+            }
+            if let Err(e) = fs::remove_file(&lock) {
+                util::log_error(
+                    &format!("Failed to remove {}: {}", lock.display(), e),
+                    debug,
+                );
+            }
+        }
+    }
+
     /// Update mtime
     fn touch(&self) {
         use std::os::unix::ffi::OsStrExt;
@@ -264,6 +638,115 @@ impl DbCache {
     }
 }
 
+/// AUR RPC response cache at ~/.cache/pacfetch/aur.json
+struct AurCache {
+    path: PathBuf,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AurCacheFile {
+    rpc_url: String,
+    /// name -> AUR version, null for packages that are not in the AUR
+    packages: HashMap<String, Option<String>>,
+}
+
+impl AurCache {
+    fn new() -> Option<Self> {
+        let cache_dir = crate::config::Config::cache_dir()?;
+        let cache_path = cache_dir.parent()?;
+
+        fs::create_dir_all(cache_path).ok()?;
+
+        Some(Self {
+            path: cache_path.join("aur.json"),
+        })
+    }
+
+    /// Cached AUR versions, if fresh, from the same endpoint, and covering
+    /// every requested package
+    fn load(
+        &self,
+        ttl_minutes: u32,
+        rpc_url: &str,
+        names: &[&str],
+    ) -> Option<HashMap<String, Option<String>>> {
+        let contents = read_fresh_cache(&self.path, ttl_minutes)?;
+        let cached: AurCacheFile = serde_json::from_str(&contents).ok()?;
+
+        if cached.rpc_url == rpc_url && names.iter().all(|n| cached.packages.contains_key(*n)) {
+            Some(cached.packages)
+        } else {
+            None
+        }
+    }
+
+    fn store(&self, rpc_url: &str, packages: HashMap<String, Option<String>>) {
+        let file = AurCacheFile {
+            rpc_url: rpc_url.to_string(),
+            packages,
+        };
+        if let Ok(json) = serde_json::to_string(&file) {
+            let _ = fs::write(&self.path, json);
+        }
+    }
+}
+
+/// Security tracker response cache at ~/.cache/pacfetch/security.json
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SecurityCacheFile {
+    url: String,
+    groups: Vec<AdvisoryGroup>,
+}
+
+/// An advisory group (AVG) from the Arch security tracker
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct AdvisoryGroup {
+    packages: Vec<String>,
+    status: String,
+    severity: String,
+    fixed: Option<String>,
+}
+
+fn security_cache_path() -> Option<PathBuf> {
+    let cache_dir = crate::config::Config::cache_dir()?;
+    let cache_path = cache_dir.parent()?;
+    fs::create_dir_all(cache_path).ok()?;
+    Some(cache_path.join("security.json"))
+}
+
+/// Contents of a cache file, if it is younger than the TTL
+fn read_fresh_cache(path: &std::path::Path, ttl_minutes: u32) -> Option<String> {
+    if ttl_minutes == 0 {
+        return None;
+    }
+
+    let age = fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()?;
+    if age.as_secs() > (ttl_minutes as u64 * 60) {
+        return None;
+    }
+
+    fs::read_to_string(path).ok()
+}
+
+/// Held while the temp databases sync. libalpm keeps db.lck in the cache dir
+/// until the sync ends, so the process waits on this before exiting. Set to
+/// true once it is exiting, so an abandoned collector doesn't start a sync.
+static TEMP_SYNC: Mutex<bool> = Mutex::new(false);
+
+/// Wait for a temp database sync still running past the `[performance]`
+/// budget, and keep any later one from starting
+pub fn finish_temp_sync() {
+    *TEMP_SYNC.lock().unwrap_or_else(|e| e.into_inner()) = true;
+}
+
+/// Update the spinner unless it was already cleared for rendering, which a
+/// collector past the budget can outlive
+fn set_spinner_message(spinner: Option<&ProgressBar>, msg: String) {
+    if let Some(pb) = spinner.filter(|pb| !pb.is_finished()) {
+        pb.set_message(msg);
+    }
+}
+
 fn calculate_upgrade_stats_with_sync(
     spinner: Option<&ProgressBar>,
     debug: bool,
@@ -287,133 +770,92 @@ fn calculate_upgrade_stats_with_sync(
                 ttl_minutes
             );
         }
-        if let Some(pb) = spinner {
-            pb.set_message("Using cached databases");
+        if spinner.is_some() {
+            set_spinner_message(spinner, "Using cached databases".to_string());
             std::thread::sleep(std::time::Duration::from_millis(100));
-            pb.set_message("Gathering stats");
+            set_spinner_message(spinner, "Gathering stats".to_string());
         }
 
         let calc_start = Instant::now();
-        let stats = calculate_upgrade_stats(cache.dbpath(), debug);
+        let mut stats = calculate_upgrade_stats(cache.dbpath(), debug);
+        stats.mirror_url = cache.served_mirror();
         if debug {
             eprintln!("  Stats calculation: {:?}", calc_start.elapsed());
         }
         return stats;
     }
 
-    // not fres
+    // not fresh
+    let exiting = TEMP_SYNC.lock().unwrap_or_else(|e| e.into_inner());
+    if *exiting {
+        return fail;
+    }
+    cache.remove_stale_lock(debug);
     cache.copy_system_dbs();
 
     let sync_start = Instant::now();
-
-    let cmd = if util::is_root() {
-        format!("pacman -Sy --dbpath {} --logfile /dev/null", cache.dbpath())
-    } else {
-        format!(
-            "fakeroot -- pacman -Sy --disable-sandbox-filesystem --dbpath {} --logfile /dev/null",
-            cache.dbpath()
-        )
-    };
-
-    let mut session = match expectrl::spawn(&cmd) {
-        Ok(s) => s,
-        Err(e) => {
-            util::log_error(&format!("Failed to spawn pacman: {}", e), debug);
-            return fail;
-        }
-    };
-
-    session.set_expect_timeout(Some(std::time::Duration::from_millis(100)));
-
-    let mut progress = SyncProgress::new();
-    if let Some(pb) = spinner {
-        pb.set_message(format!("Syncing databases: {}", progress.format()));
-    }
-
-    let mut line_buffer = String::new();
-    let mut sync_success = false;
-
-    loop {
-        match session.is_alive() {
-            Ok(true) => {}
-            Ok(false) => {
-                if !line_buffer.is_empty() {
-                    progress.update_from_line(&line_buffer);
-                    if let Some(pb) = spinner {
-                        pb.set_message(format!("Syncing databases: {}", progress.format()));
-                    }
+    match sync_dbs(cache.dbpath(), spinner, debug) {
+        Ok(served) => {
+            if debug {
+                for (repo, base) in &served {
+                    eprintln!("  {} served by {}", repo, base);
                 }
-                sync_success = true;
-                break;
             }
-            Err(_) => break,
+            cache.set_served_mirrors(&served);
         }
-
-        let mut buf = [0u8; 1024];
-        match session.try_read(&mut buf) {
-            Ok(0) => continue,
-            Ok(n) => {
-                let chunk = String::from_utf8_lossy(&buf[..n]);
-
-                for ch in chunk.chars() {
-                    if ch == '\n' || ch == '\r' {
-                        if !line_buffer.is_empty() {
-                            progress.update_from_line(&line_buffer);
-                            if let Some(pb) = spinner {
-                                pb.set_message(format!("Syncing databases: {}", progress.format()));
-                            }
-                        }
-                        line_buffer.clear();
-                    } else {
-                        line_buffer.push(ch);
-                    }
-                }
-            }
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted => {
-                    std::thread::sleep(std::time::Duration::from_millis(10));
-                }
-                _ => break,
-            },
+        Err(e) => {
+            util::log_error(&e, debug);
+            return fail;
         }
     }
 
-    if !sync_success {
-        util::log_error("Database sync failed or was interrupted", debug);
-        return fail;
-    }
-
     // Mark cache as fresh
     cache.touch();
+    drop(exiting);
 
     if debug {
         eprintln!("  Database sync: {:?}", sync_start.elapsed());
     }
 
-    if let Some(pb) = spinner {
-        progress.core = DbSyncState::Complete;
-        progress.extra = DbSyncState::Complete;
-        progress.multilib = DbSyncState::Complete;
-        pb.set_message(format!("Syncing databases: {}", progress.format()));
+    if spinner.is_some() {
         std::thread::sleep(std::time::Duration::from_millis(100));
-        pb.set_message("Gathering stats");
+        set_spinner_message(spinner, "Gathering stats".to_string());
     }
 
     let calc_start = Instant::now();
-    let stats = calculate_upgrade_stats(cache.dbpath(), debug);
+    let mut stats = calculate_upgrade_stats(cache.dbpath(), debug);
+    stats.mirror_url = cache.served_mirror();
     if debug {
         eprintln!("  Stats calculation: {:?}", calc_start.elapsed());
     }
     stats
 }
 
-fn get_installed_count() -> u32 {
-    let output = Command::new("pacman").arg("-Q").output().unwrap();
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    stdout.lines().count() as u32
+/// Whether the temp databases were synced within the cache TTL, so a
+/// fresh-sync run would reuse them without touching the network
+pub fn temp_dbs_fresh(ttl_minutes: u32) -> bool {
+    DbCache::new().is_some_and(|cache| cache.is_fresh(ttl_minutes))
+}
+
+/// Age in seconds of the oldest `.db` file in a sync dir
+fn sync_db_age(sync_dir: &Path) -> Option<u64> {
+    fs::read_dir(sync_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "db"))
+        .filter_map(|e| e.metadata().ok()?.modified().ok()?.elapsed().ok())
+        .map(|age| age.as_secs())
+        .max()
 }
 
-fn get_seconds_since_update() -> Option<i64> {
+/// Installed package counts from the local db, as (total, explicit)
+fn get_installed_counts(session: &PackageSession) -> Option<(u32, u32)> {
+    let pkgs = session.local()?;
+    let explicit = pkgs.iter().filter(|p| !p.is_dep).count();
+    Some((pkgs.len() as u32, explicit as u32))
+}
+
+fn get_last_update_time() -> Option<DateTime<Local>> {
     let contents = fs::read_to_string("/var/log/pacman.log").expect("Failed to read pacman.log");
 
     let mut saw_upgrade_start = false;
@@ -445,50 +887,362 @@ fn get_seconds_since_update() -> Option<i64> {
 
         let parsed: DateTime<FixedOffset> = DateTime::parse_from_rfc3339(&formatted_date).unwrap();
 
-        let last_update_local = parsed.with_timezone(&Local);
-        let now = Local::now();
-        let duration = now.signed_duration_since(last_update_local);
-        let seconds = duration.num_seconds().max(0);
-
-        return Some(seconds);
+        return Some(parsed.with_timezone(&Local));
     }
 
     None
 }
 
-/// Calculate upgrade stats from a db path
-fn calculate_upgrade_stats(dbpath: &str, debug: bool) -> UpgradeStats {
-    let fail = UpgradeStats::default();
+/// pacman's built-in SigLevel when pacman.conf doesn't set one
+const DEFAULT_SIGLEVEL: alpm::SigLevel = alpm::SigLevel::PACKAGE
+    .union(alpm::SigLevel::PACKAGE_OPTIONAL)
+    .union(alpm::SigLevel::DATABASE)
+    .union(alpm::SigLevel::DATABASE_OPTIONAL);
+
+/// Apply a pacman.conf `SigLevel = ...` value on top of `level`, the way pacman does
+fn apply_siglevel(mut level: alpm::SigLevel, value: &str) -> alpm::SigLevel {
+    use alpm::SigLevel as S;
+
+    for token in value.split_whitespace() {
+        let (package, database, option) = if let Some(opt) = token.strip_prefix("Package") {
+            (true, false, opt)
+        } else if let Some(opt) = token.strip_prefix("Database") {
+            (false, true, opt)
+        } else {
+            (true, true, token)
+        };
 
-    let mut alpm = match Alpm::new("/", dbpath) {
-        Ok(a) => a,
-        Err(e) => {
-            util::log_error(&format!("Failed to initialize alpm: {}", e), debug);
-            return fail;
+        let targets = [
+            (
+                package,
+                S::PACKAGE,
+                S::PACKAGE_OPTIONAL,
+                S::PACKAGE_MARGINAL_OK | S::PACKAGE_UNKNOWN_OK,
+            ),
+            (
+                database,
+                S::DATABASE,
+                S::DATABASE_OPTIONAL,
+                S::DATABASE_MARGINAL_OK | S::DATABASE_UNKNOWN_OK,
+            ),
+        ];
+        for (enabled, check, optional, trust) in targets {
+            if !enabled {
+                continue;
+            }
+            match option {
+                "Never" => level.remove(check),
+                "Optional" => level.insert(check | optional),
+                "Required" => {
+                    level.insert(check);
+                    level.remove(optional);
+                }
+                "TrustedOnly" => level.remove(trust),
+                "TrustAll" => level.insert(trust),
+                _ => {}
+            }
         }
-    };
-
-    let _ = alpm.register_syncdb_mut("core", alpm::SigLevel::NONE);
-    let _ = alpm.register_syncdb_mut("extra", alpm::SigLevel::NONE);
-    let _ = alpm.register_syncdb_mut("multilib", alpm::SigLevel::NONE);
-
-    if let Err(e) = alpm.trans_init(alpm::TransFlag::NO_LOCK) {
-        util::log_error(&format!("Failed to init transaction: {}", e), debug);
-        return fail;
     }
+    level
+}
 
-    if let Err(e) = alpm.sync_sysupgrade(false) {
-        let msg = format!("Failed to sync sysupgrade: {}", e);
-        let _ = alpm.trans_release();
-        util::log_error(&msg, debug);
-        return fail;
-    }
+/// Parse the repos of a pacman.conf. `Include`d mirrorlists are read for servers.
+fn parse_sync_repos(contents: &str) -> Vec<SyncRepo> {
+    let mut global: Option<String> = None;
+    let mut arch = std::env::consts::ARCH.to_string();
+    let mut repos: Vec<(String, Option<String>, Vec<String>)> = Vec::new();
+    let mut in_options = false;
 
-    let prepare_err = alpm.trans_prepare().err().map(|e| format!("{}", e));
-    if let Some(msg) = prepare_err {
-        let _ = alpm.trans_release();
-        util::log_error(&format!("Failed to prepare transaction: {}", msg), debug);
-        return fail;
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_options = section == "options";
+            if !in_options {
+                repos.push((section.to_string(), None, Vec::new()));
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match (key.trim(), in_options, repos.last_mut()) {
+            ("SigLevel", true, _) => global = Some(value),
+            ("Architecture", true, _) => {
+                if let Some(first) = value.split_whitespace().next()
+                    && first != "auto"
+                {
+                    arch = first.to_string();
+                }
+            }
+            ("SigLevel", false, Some(repo)) => repo.1 = Some(value),
+            ("Server", false, Some(repo)) => repo.2.push(value),
+            ("Include", false, Some(repo)) => repo.2.extend(read_include_servers(&value)),
+            _ => {}
+        }
+    }
+
+    let base = apply_siglevel(DEFAULT_SIGLEVEL, global.as_deref().unwrap_or(""));
+    repos
+        .into_iter()
+        .map(|(name, level, servers)| SyncRepo {
+            siglevel: apply_siglevel(base, level.as_deref().unwrap_or("")),
+            servers: servers
+                .iter()
+                .map(|s| Mirror::new(s, &name, &arch))
+                .collect(),
+            name,
+        })
+        .collect()
+}
+
+/// `Server` values of an `Include`d file such as the mirrorlist
+fn read_include_servers(path: &str) -> Vec<String> {
+    let included = fs::read_to_string(path).unwrap_or_default();
+    included
+        .lines()
+        .filter_map(|line| {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let (key, server) = line.split_once('=')?;
+            (key.trim() == "Server").then(|| server.trim().to_string())
+        })
+        .collect()
+}
+
+/// Sync repos from /etc/pacman.conf, or the official repos served by the
+/// mirrorlist if it can't be read
+fn read_sync_repos() -> Vec<SyncRepo> {
+    match fs::read_to_string("/etc/pacman.conf") {
+        Ok(contents) => parse_sync_repos(&contents),
+        Err(_) => {
+            let servers = read_include_servers("/etc/pacman.d/mirrorlist");
+            let arch = std::env::consts::ARCH;
+            ["core", "extra", "multilib"]
+                .iter()
+                .map(|name| SyncRepo {
+                    name: name.to_string(),
+                    siglevel: DEFAULT_SIGLEVEL,
+                    servers: servers.iter().map(|s| Mirror::new(s, name, arch)).collect(),
+                })
+                .collect()
+        }
+    }
+}
+
+/// Register the pacman.conf repos with their SigLevel and servers
+fn register_sync_dbs(alpm: &mut Alpm, debug: bool) {
+    let _ = alpm.set_gpgdir("/etc/pacman.d/gnupg/");
+
+    for repo in read_sync_repos() {
+        match alpm.register_syncdb_mut(repo.name.as_str(), repo.siglevel) {
+            Ok(db) => {
+                for server in &repo.servers {
+                    let _ = db.add_server(server.url.as_str());
+                }
+            }
+            Err(e) => {
+                util::log_error(&format!("Failed to register {}: {}", repo.name, e), debug);
+            }
+        }
+    }
+}
+
+/// Check every registered sync db against its SigLevel.
+/// Returns the repos whose db failed verification.
+fn verify_sync_dbs(alpm: &Alpm, debug: bool) -> Vec<String> {
+    let mut invalid = Vec::new();
+    for db in alpm.syncdbs() {
+        match db.is_valid() {
+            // Repo never synced into this dbpath
+            Ok(()) | Err(alpm::Error::DbNotFound) => {}
+            Err(e) => {
+                util::log_error(
+                    &format!("Database {} failed verification: {}", db.name(), e),
+                    debug,
+                );
+                invalid.push(db.name().to_string());
+            }
+        }
+    }
+    invalid
+}
+
+/// Sync every repo from its mirrors in order, moving a repo on to its next
+/// mirror when a round fails it. `sync_round` syncs the given repos from the
+/// given mirrors and returns the repos that failed. Returns the mirror base
+/// that served each repo, in pacman.conf order.
+fn sync_with_failover<F>(
+    repos: &[SyncRepo],
+    debug: bool,
+    mut sync_round: F,
+) -> Result<Vec<(String, String)>, String>
+where
+    F: FnMut(&[(&SyncRepo, &Mirror)]) -> Result<Vec<String>, String>,
+{
+    let mut pending: Vec<(&SyncRepo, usize)> = repos.iter().map(|r| (r, 0)).collect();
+    let mut served: HashMap<&str, &str> = HashMap::new();
+    let mut exhausted: Vec<&str> = Vec::new();
+
+    loop {
+        pending.retain(|(repo, i)| {
+            let left = *i < repo.servers.len();
+            if !left {
+                exhausted.push(&repo.name);
+            }
+            left
+        });
+        if pending.is_empty() {
+            break;
+        }
+
+        let round: Vec<(&SyncRepo, &Mirror)> = pending
+            .iter()
+            .map(|(repo, i)| (*repo, &repo.servers[*i]))
+            .collect();
+        let failed = sync_round(&round)?;
+
+        let mut retry = Vec::new();
+        for (repo, i) in pending {
+            let mirror = &repo.servers[i];
+            if !failed.contains(&repo.name) {
+                served.insert(&repo.name, &mirror.base);
+                continue;
+            }
+            let msg = match repo.servers.get(i + 1) {
+                Some(next) => format!(
+                    "Mirror {} failed for {}, trying {}",
+                    mirror.url, repo.name, next.url
+                ),
+                None => format!(
+                    "Mirror {} failed for {}, no mirrors left",
+                    mirror.url, repo.name
+                ),
+            };
+            util::log_error(&msg, debug);
+            retry.push((repo, i + 1));
+        }
+        pending = retry;
+    }
+
+    if !exhausted.is_empty() {
+        return Err(format!(
+            "Failed to sync {}: no working mirror",
+            exhausted.join(", ")
+        ));
+    }
+    Ok(repos
+        .iter()
+        .filter_map(|r| {
+            served
+                .get(r.name.as_str())
+                .map(|base| (r.name.clone(), base.to_string()))
+        })
+        .collect())
+}
+
+/// Sync the pacman.conf repos into `dbpath` through libalpm, with per-repo
+/// progress on the spinner and mirror failover. Returns the mirror base that
+/// served each repo.
+fn sync_dbs(
+    dbpath: &str,
+    spinner: Option<&ProgressBar>,
+    debug: bool,
+) -> Result<Vec<(String, String)>, String> {
+    sync_repos(dbpath, &read_sync_repos(), spinner, debug)
+}
+
+fn sync_repos(
+    dbpath: &str,
+    repos: &[SyncRepo],
+    spinner: Option<&ProgressBar>,
+    debug: bool,
+) -> Result<Vec<(String, String)>, String> {
+    let mut alpm =
+        Alpm::new("/", dbpath).map_err(|e| format!("Failed to initialize alpm: {}", e))?;
+    if !util::is_root() {
+        // Landlock can't be set up for an unprivileged user's cache dir
+        alpm.set_disable_sandbox_filesystem(true);
+    }
+    let _ = alpm.set_gpgdir("/etc/pacman.d/gnupg/");
+
+    alpm.set_parallel_downloads(repos.len().max(1) as u32);
+
+    let names = repos.iter().map(|r| r.name.clone()).collect();
+    let progress = Rc::new(RefCell::new(SyncProgress::new(names)));
+    set_spinner_message(
+        spinner,
+        format!("Syncing databases: {}", progress.borrow().format()),
+    );
+    let cb_progress = Rc::clone(&progress);
+    let cb_spinner = spinner.cloned();
+    alpm.set_dl_cb((), move |filename, event, _| {
+        let mut progress = cb_progress.borrow_mut();
+        progress.update(filename, event.event(), debug);
+        set_spinner_message(
+            cb_spinner.as_ref(),
+            format!("Syncing databases: {}", progress.format()),
+        );
+    });
+
+    sync_with_failover(repos, debug, |round| {
+        // Each round syncs only the repos still missing, from one mirror each
+        let _ = alpm.unregister_all_syncdbs();
+        for (repo, mirror) in round {
+            let db = alpm
+                .register_syncdb_mut(repo.name.as_str(), repo.siglevel)
+                .map_err(|e| format!("Failed to register {}: {}", repo.name, e))?;
+            let _ = db.add_server(mirror.url.as_str());
+            progress.borrow_mut().restart(&repo.name);
+        }
+
+        let result = alpm.syncdbs_mut().update(false);
+        let failed = progress.borrow().failed();
+        match result {
+            Err(e) if failed.is_empty() => Err(format!("Failed to sync databases: {}", e)),
+            _ => Ok(failed),
+        }
+    })
+}
+
+/// Calculate upgrade stats from a db path
+fn calculate_upgrade_stats(dbpath: &str, debug: bool) -> UpgradeStats {
+    let fail = UpgradeStats::default();
+
+    let mut alpm = match Alpm::new("/", dbpath) {
+        Ok(a) => a,
+        Err(e) => {
+            util::log_error(&format!("Failed to initialize alpm: {}", e), debug);
+            return fail;
+        }
+    };
+
+    register_sync_dbs(&mut alpm, debug);
+    let invalid_repos = verify_sync_dbs(&alpm, debug);
+    if !invalid_repos.is_empty() {
+        return UpgradeStats {
+            invalid_repos,
+            ..fail
+        };
+    }
+
+    if let Err(e) = alpm.trans_init(alpm::TransFlag::NO_LOCK) {
+        util::log_error(&format!("Failed to init transaction: {}", e), debug);
+        return fail;
+    }
+
+    if let Err(e) = alpm.sync_sysupgrade(false) {
+        let msg = format!("Failed to sync sysupgrade: {}", e);
+        let _ = alpm.trans_release();
+        util::log_error(&msg, debug);
+        return fail;
+    }
+
+    let prepare_err = alpm.trans_prepare().err().map(|e| format!("{}", e));
+    if let Some(msg) = prepare_err {
+        let _ = alpm.trans_release();
+        util::log_error(&format!("Failed to prepare transaction: {}", msg), debug);
+        return fail;
     }
 
     let localdb = alpm.localdb();
@@ -497,9 +1251,11 @@ fn calculate_upgrade_stats(dbpath: &str, debug: bool) -> UpgradeStats {
     let mut total_installed_size: i64 = 0;
     let mut net_upgrade_size: i64 = 0;
     let mut package_count: u32 = 0;
+    let mut pending_versions = HashMap::new();
 
     for pkg in alpm.trans_add().into_iter() {
         package_count += 1;
+        pending_versions.insert(pkg.name().to_string(), pkg.version().to_string());
         total_download_size += pkg.download_size();
         let new_size = pkg.isize();
         total_installed_size += new_size;
@@ -516,55 +1272,600 @@ fn calculate_upgrade_stats(dbpath: &str, debug: bool) -> UpgradeStats {
         net_upgrade_size -= pkg.isize();
     }
 
+    let keyring_version = localdb
+        .pkg(KEYRING_PACKAGE)
+        .ok()
+        .map(|pkg| pkg.version().to_string());
+
     let _ = alpm.trans_release();
 
-    let download_mib = total_download_size as f64 / BYTES_PER_MIB;
-    let installed_mib = total_installed_size as f64 / BYTES_PER_MIB;
-    let mut net_mib = net_upgrade_size as f64 / BYTES_PER_MIB;
+    UpgradeStats {
+        download_size_bytes: Some(total_download_size),
+        installed_size_bytes: Some(total_installed_size),
+        net_upgrade_size_bytes: Some(net_upgrade_size),
+        package_count: Some(package_count),
+        pending_versions,
+        keyring_version,
+        invalid_repos: Vec::new(),
+        mirror_url: None,
+    }
+}
 
-    if net_mib > -0.01 && net_mib < 0.01 {
-        net_mib = 0.0;
+fn get_orphaned_packages(session: &PackageSession) -> (Option<u32>, Option<i64>) {
+    let Some(pkgs) = session.local() else {
+        return (None, None);
+    };
+
+    let mut count = 0;
+    let mut total_size: i64 = 0;
+
+    for pkg in pkgs {
+        if pkg.is_dep && pkg.required_by.is_empty() && !pkg.optional_for {
+            count += 1;
+            total_size += pkg.size;
+        }
     }
 
-    UpgradeStats {
-        download_size_mb: Some(download_mib),
-        installed_size_mb: Some(installed_mib),
-        net_upgrade_size_mb: Some(net_mib),
-        package_count,
+    (Some(count), Some(total_size))
+}
+
+/// An installed package, as read from the local db
+struct LocalPkg {
+    name: String,
+    version: String,
+    size: i64,
+    /// Installed as a dependency rather than explicitly
+    is_dep: bool,
+    required_by: Vec<String>,
+    /// Some package lists it as an optional dependency
+    optional_for: bool,
+}
+
+/// The local and sync dbs for one run. Each is read at most once, on first
+/// use, and shared by every collector thread that needs it.
+struct PackageSession {
+    debug: bool,
+    /// One alpm handle for the run, opened on first use. None if it failed to open
+    alpm: OnceLock<Option<Mutex<SessionAlpm>>>,
+    local: OnceLock<Option<Vec<LocalPkg>>>,
+    /// Package names across the sync dbs, None if a db failed verification
+    repo_names: OnceLock<Result<HashSet<String>, SyncDbsError>>,
+}
+
+/// An alpm handle that can be shared between collector threads
+struct SessionAlpm(Alpm);
+
+// SAFETY: the handle is only ever reached through the session's Mutex, so
+// libalpm never sees two threads at once.
+unsafe impl Send for SessionAlpm {}
+
+/// Why the sync db package names couldn't be read
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SyncDbsError {
+    Unreadable,
+    InvalidSignature,
+}
+
+impl PackageSession {
+    fn new(debug: bool) -> Self {
+        Self {
+            debug,
+            alpm: OnceLock::new(),
+            local: OnceLock::new(),
+            repo_names: OnceLock::new(),
+        }
+    }
+
+    /// Run `f` on the session's alpm handle, opening it on first use
+    fn with_alpm<T>(&self, f: impl FnOnce(&mut Alpm) -> T) -> Option<T> {
+        let handle = self
+            .alpm
+            .get_or_init(|| match Alpm::new("/", "/var/lib/pacman") {
+                Ok(alpm) => Some(Mutex::new(SessionAlpm(alpm))),
+                Err(e) => {
+                    util::log_error(&format!("Failed to open the pacman db: {}", e), self.debug);
+                    None
+                }
+            });
+        let mut guard = handle.as_ref()?.lock().unwrap_or_else(|e| e.into_inner());
+        Some(f(&mut guard.0))
+    }
+
+    fn local(&self) -> Option<&[LocalPkg]> {
+        self.local
+            .get_or_init(|| {
+                self.with_alpm(|alpm| {
+                    alpm.localdb()
+                        .pkgs()
+                        .into_iter()
+                        .map(|pkg| LocalPkg {
+                            name: pkg.name().to_string(),
+                            version: pkg.version().to_string(),
+                            size: pkg.isize(),
+                            is_dep: pkg.reason() == alpm::PackageReason::Depend,
+                            required_by: pkg.required_by().into_iter().collect(),
+                            optional_for: !pkg.optional_for().is_empty(),
+                        })
+                        .collect()
+                })
+            })
+            .as_deref()
+    }
+
+    fn repo_names(&self) -> Result<&HashSet<String>, SyncDbsError> {
+        self.repo_names
+            .get_or_init(|| {
+                self.with_alpm(|alpm| {
+                    // Packages missing from an unverified db would be misreported as foreign
+                    register_sync_dbs(alpm, self.debug);
+                    if !verify_sync_dbs(alpm, self.debug).is_empty() {
+                        return Err(SyncDbsError::InvalidSignature);
+                    }
+                    Ok(alpm
+                        .syncdbs()
+                        .into_iter()
+                        .flat_map(|db| db.pkgs().into_iter().map(|pkg| pkg.name().to_string()))
+                        .collect())
+                })
+                .unwrap_or(Err(SyncDbsError::Unreadable))
+            })
+            .as_ref()
+            .map_err(|e| *e)
     }
 }
 
-fn get_orphaned_packages(debug: bool) -> (Option<u32>, Option<f64>) {
-    let alpm = match Alpm::new("/", "/var/lib/pacman") {
-        Ok(a) => a,
+/// Dependencies installed only for `root`: installed as dependencies and
+/// required by nothing outside root and its other exclusive dependencies
+fn exclusive_deps(root: usize, pkgs: &[LocalPkg], children: &[Vec<usize>]) -> Vec<usize> {
+    let mut members: HashSet<&str> = HashSet::from([pkgs[root].name.as_str()]);
+    let mut result = Vec::new();
+
+    loop {
+        let mut added = false;
+        let frontier: Vec<usize> = std::iter::once(root)
+            .chain(result.iter().copied())
+            .flat_map(|i| children[i].iter().copied())
+            .collect();
+
+        for dep in frontier {
+            let pkg = &pkgs[dep];
+            if pkg.is_dep
+                && !members.contains(pkg.name.as_str())
+                && pkg.required_by.iter().all(|r| members.contains(r.as_str()))
+            {
+                members.insert(pkg.name.as_str());
+                result.push(dep);
+                added = true;
+            }
+        }
+
+        if !added {
+            return result;
+        }
+    }
+}
+
+fn rank_largest(pkgs: &[LocalPkg], count: usize, with_exclusive_deps: bool) -> Vec<PackageSize> {
+    let mut ranked: Vec<PackageSize> = if with_exclusive_deps {
+        let index: HashMap<&str, usize> = pkgs
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.name.as_str(), i))
+            .collect();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); pkgs.len()];
+        for (i, pkg) in pkgs.iter().enumerate() {
+            for parent in &pkg.required_by {
+                if let Some(&p) = index.get(parent.as_str()) {
+                    children[p].push(i);
+                }
+            }
+        }
+
+        let deps: Vec<Vec<usize>> = (0..pkgs.len())
+            .map(|i| exclusive_deps(i, pkgs, &children))
+            .collect();
+        // Packages folded into another package's total aren't listed on their own
+        let absorbed: HashSet<usize> = deps.iter().flatten().copied().collect();
+
+        pkgs.iter()
+            .enumerate()
+            .filter(|(i, _)| !absorbed.contains(i))
+            .map(|(i, pkg)| PackageSize {
+                name: pkg.name.clone(),
+                size_bytes: pkg.size + deps[i].iter().map(|&d| pkgs[d].size).sum::<i64>(),
+                exclusive_deps: deps[i].len() as u32,
+            })
+            .collect()
+    } else {
+        pkgs.iter()
+            .map(|pkg| PackageSize {
+                name: pkg.name.clone(),
+                size_bytes: pkg.size,
+                exclusive_deps: 0,
+            })
+            .collect()
+    };
+
+    ranked.sort_by(|a, b| {
+        b.size_bytes
+            .cmp(&a.size_bytes)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    ranked.truncate(count);
+    ranked
+}
+
+fn get_largest_packages(
+    config: &crate::config::LargestPackagesConfig,
+    session: &PackageSession,
+) -> Option<Vec<PackageSize>> {
+    let pkgs = session.local()?;
+    Some(rank_largest(pkgs, config.count, config.exclusive_deps))
+}
+
+/// Latest change per package, built from pacman.log lines fed newest first.
+/// Packages removed after their last change are dropped.
+struct RecentScan {
+    now: DateTime<FixedOffset>,
+    count: usize,
+    days: Option<u32>,
+    /// Packages whose latest entry has already been seen
+    seen: HashSet<String>,
+    recent: Vec<RecentPackage>,
+}
+
+impl RecentScan {
+    fn new(now: DateTime<FixedOffset>, count: usize, days: Option<u32>) -> Self {
+        Self {
+            now,
+            count,
+            days,
+            seen: HashSet::new(),
+            recent: Vec::new(),
+        }
+    }
+
+    /// Take the next older line; false once older lines can't change the result
+    fn push(&mut self, line: &str) -> bool {
+        let Some((ts, rest)) = line.trim().split_once("] [ALPM] ") else {
+            return true;
+        };
+        let Ok(when) = DateTime::parse_from_str(ts.trim_start_matches('['), "%Y-%m-%dT%H:%M:%S%z")
+        else {
+            return true;
+        };
+        let seconds_ago = self.now.signed_duration_since(when).num_seconds().max(0);
+        if self
+            .days
+            .is_some_and(|d| seconds_ago > i64::from(d) * 86400)
+        {
+            return false;
+        }
+        // Entries sharing the last kept timestamp can still win the name tiebreak
+        if self.recent.len() >= self.count
+            && self
+                .recent
+                .last()
+                .is_none_or(|p| seconds_ago > p.seconds_ago)
+        {
+            return false;
+        }
+
+        let Some((action, rest)) = rest.split_once(' ') else {
+            return true;
+        };
+        let Some((name, version)) = rest.split_once(" (") else {
+            return true;
+        };
+        let change = match action {
+            "installed" => Some(PackageChange::Installed),
+            "upgraded" => Some(PackageChange::Upgraded),
+            "downgraded" => Some(PackageChange::Downgraded),
+            "reinstalled" => Some(PackageChange::Reinstalled),
+            "removed" => None,
+            _ => return true,
+        };
+        if !self.seen.insert(name.to_string()) {
+            return true;
+        }
+        if let Some(change) = change {
+            // "old -> new" for upgrades and downgrades
+            let version = version.trim_end_matches(')');
+            let version = version.rsplit(" -> ").next().unwrap_or(version);
+            self.recent.push(RecentPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+                change,
+                seconds_ago,
+            });
+        }
+        true
+    }
+
+    fn finish(mut self) -> Vec<RecentPackage> {
+        self.recent.sort_by(|a, b| {
+            a.seconds_ago
+                .cmp(&b.seconds_ago)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        self.recent.truncate(self.count);
+        self.recent
+    }
+}
+
+/// Feed `f` the lines of `path` from last to first until it returns false
+fn read_lines_rev(path: &Path, mut f: impl FnMut(&str) -> bool) -> std::io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = fs::File::open(path)?;
+    let mut pos = file.metadata()?.len();
+    // Start of a line that continues into the chunk read before it
+    let mut carry: Vec<u8> = Vec::new();
+
+    while pos > 0 {
+        let len = LOG_CHUNK_BYTES.min(pos);
+        pos -= len;
+        let mut buf = vec![0; len as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut buf)?;
+        buf.extend_from_slice(&carry);
+
+        // Until the start of the file, the first line may be cut short
+        let complete = if pos > 0 {
+            match buf.iter().position(|&b| b == b'\n') {
+                Some(i) => i + 1,
+                None => {
+                    carry = buf;
+                    continue;
+                }
+            }
+        } else {
+            0
+        };
+        for line in buf[complete..].rsplit(|&b| b == b'\n') {
+            if !line.is_empty() && !f(&String::from_utf8_lossy(line)) {
+                return Ok(());
+            }
+        }
+        carry = buf[..complete.saturating_sub(1)].to_vec();
+    }
+    Ok(())
+}
+
+fn get_recent_packages(
+    config: &crate::config::RecentPackagesConfig,
+    debug: bool,
+) -> Option<Vec<RecentPackage>> {
+    let mut scan = RecentScan::new(Local::now().fixed_offset(), config.count, config.days);
+    if let Err(e) = read_lines_rev(Path::new(PACMAN_LOG_PATH), |line| scan.push(line)) {
+        util::log_error(&format!("Failed to read pacman.log: {}", e), debug);
+        return None;
+    }
+    Some(scan.finish())
+}
+
+fn get_flatpak_updates(flatpak_path: &str, debug: bool) -> Option<FlatpakUpdates> {
+    let list = |installation: &str| -> Option<Vec<String>> {
+        let output = match Command::new(flatpak_path)
+            .args([
+                "remote-ls",
+                "--updates",
+                installation,
+                "--columns=application",
+            ])
+            .output()
+        {
+            Ok(o) => o,
+            Err(e) => {
+                util::log_error(&format!("Failed to run {}: {}", flatpak_path, e), debug);
+                return None;
+            }
+        };
+        if !output.status.success() {
+            util::log_error(
+                &format!(
+                    "{} remote-ls {} failed: {}",
+                    flatpak_path,
+                    installation,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                debug,
+            );
+            return None;
+        }
+        Some(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect(),
+        )
+    };
+
+    Some(FlatpakUpdates {
+        system: list("--system")?,
+        user: list("--user")?,
+    })
+}
+
+fn fetch_advisories(
+    security: &crate::config::SecurityConfig,
+    ttl_minutes: u32,
+    debug: bool,
+) -> Option<Vec<AdvisoryGroup>> {
+    let cache_path = security_cache_path();
+
+    if let Some(contents) = cache_path
+        .as_deref()
+        .and_then(|p| read_fresh_cache(p, ttl_minutes))
+        && let Ok(cached) = serde_json::from_str::<SecurityCacheFile>(&contents)
+        && cached.url == security.url
+    {
+        if debug {
+            eprintln!(
+                "  Security tracker: SKIP (cache fresh, TTL {}min)",
+                ttl_minutes
+            );
+        }
+        return Some(cached.groups);
+    }
+
+    let client = match reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(security.timeout_secs))
+        .build()
+    {
+        Ok(c) => c,
         Err(e) => {
+            util::log_error(&format!("Failed to build HTTP client: {}", e), debug);
+            return None;
+        }
+    };
+
+    let response = match client.get(&security.url).send() {
+        Ok(r) if r.status().is_success() => r,
+        Ok(r) => {
             util::log_error(
-                &format!("Failed to init alpm for orphan check: {}", e),
+                &format!("Security tracker returned status {}", r.status()),
                 debug,
             );
-            return (None, None);
+            return None;
+        }
+        Err(e) => {
+            util::log_error(&format!("Failed to fetch {}: {}", security.url, e), debug);
+            return None;
         }
     };
 
-    let localdb = alpm.localdb();
-    let mut count = 0;
-    let mut total_size: i64 = 0;
+    let body = match response.text() {
+        Ok(t) => t,
+        Err(e) => {
+            util::log_error(&format!("Failed to read response: {}", e), debug);
+            return None;
+        }
+    };
 
-    for pkg in localdb.pkgs().into_iter() {
-        if pkg.reason() == alpm::PackageReason::Depend
-            && pkg.required_by().is_empty()
-            && pkg.optional_for().is_empty()
-        {
-            count += 1;
-            total_size += pkg.isize();
+    let groups: Vec<AdvisoryGroup> = match serde_json::from_str(&body) {
+        Ok(g) => g,
+        Err(e) => {
+            util::log_error(
+                &format!("Failed to parse security tracker response: {}", e),
+                debug,
+            );
+            return None;
+        }
+    };
+
+    if let Some(path) = cache_path {
+        let file = SecurityCacheFile {
+            url: security.url.clone(),
+            groups,
+        };
+        if let Ok(json) = serde_json::to_string(&file) {
+            let _ = fs::write(&path, json);
         }
+        return Some(file.groups);
     }
 
-    let size_mb = total_size as f64 / BYTES_PER_MIB;
-    (Some(count), Some(size_mb))
+    Some(groups)
 }
 
-fn get_cache_size() -> Option<f64> {
+fn severity_rank(severity: &str) -> usize {
+    SEVERITIES
+        .iter()
+        .position(|s| *s == severity)
+        .unwrap_or(SEVERITIES.len() - 1)
+}
+
+/// Match advisories against installed versions the way arch-audit does:
+/// a package is vulnerable if installed < fixed, or at any version while
+/// the group has no fix yet
+fn match_advisories(
+    groups: &[AdvisoryGroup],
+    installed: &HashMap<String, String>,
+    pending: &HashMap<String, String>,
+) -> VulnerablePackages {
+    let mut found: HashMap<&str, VulnerablePackage> = HashMap::new();
+
+    for group in groups {
+        if !matches!(group.status.as_str(), "Vulnerable" | "Fixed" | "Testing") {
+            continue;
+        }
+
+        for name in &group.packages {
+            let Some(version) = installed.get(name) else {
+                continue;
+            };
+
+            let unfixed = group
+                .fixed
+                .as_deref()
+                .is_none_or(|fixed| alpm::vercmp(version.as_str(), fixed) == Ordering::Less);
+            if !unfixed {
+                continue;
+            }
+
+            let entry = found
+                .entry(name.as_str())
+                .or_insert_with(|| VulnerablePackage {
+                    name: name.clone(),
+                    installed_version: version.clone(),
+                    severity: group.severity.clone(),
+                    fixed_version: group.fixed.clone(),
+                    fixed_by_upgrade: false,
+                });
+
+            // Multiple AVGs for one package: keep the worst severity, and
+            // the highest fix, or none while any advisory is unfixed
+            if severity_rank(&group.severity) < severity_rank(&entry.severity) {
+                entry.severity = group.severity.clone();
+            }
+            entry.fixed_version = match (entry.fixed_version.take(), group.fixed.as_deref()) {
+                (Some(current), Some(fixed))
+                    if alpm::vercmp(fixed, current.as_str()) == Ordering::Greater =>
+                {
+                    Some(fixed.to_string())
+                }
+                (current, Some(_)) => current,
+                (_, None) => None,
+            };
+        }
+    }
+
+    for pkg in found.values_mut() {
+        pkg.fixed_by_upgrade = match (pkg.fixed_version.as_deref(), pending.get(&pkg.name)) {
+            (Some(fixed), Some(new)) => alpm::vercmp(new.as_str(), fixed) != Ordering::Less,
+            _ => false,
+        };
+    }
+
+    let mut packages: Vec<VulnerablePackage> = found.into_values().collect();
+    packages.sort_by(|a, b| {
+        severity_rank(&a.severity)
+            .cmp(&severity_rank(&b.severity))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    VulnerablePackages { packages }
+}
+
+fn get_vulnerable_packages(
+    config: &crate::config::Config,
+    pending: &HashMap<String, String>,
+    session: &PackageSession,
+    debug: bool,
+) -> Option<VulnerablePackages> {
+    let groups = fetch_advisories(&config.security, config.cache.security_ttl_minutes, debug)?;
+
+    let installed: HashMap<String, String> = session
+        .local()?
+        .iter()
+        .map(|pkg| (pkg.name.clone(), pkg.version.clone()))
+        .collect();
+
+    Some(match_advisories(&groups, &installed, pending))
+}
+
+fn get_cache_size() -> Option<u64> {
     let cache_path = std::path::Path::new("/var/cache/pacman/pkg");
 
     if let Ok(entries) = std::fs::read_dir(cache_path) {
@@ -575,7 +1876,7 @@ fn get_cache_size() -> Option<f64> {
             .map(|m| m.len())
             .sum();
 
-        Some(total_size as f64 / BYTES_PER_MIB)
+        Some(total_size)
     } else {
         None
     }
@@ -615,20 +1916,52 @@ fn get_mirror_url() -> Option<String> {
     None
 }
 
+/// "Pacman vX - libalpm vY". libalpm's version comes from the linked
+/// library; pacman's is probed once per binary and cached by its mtime.
 fn get_pacman_version() -> Option<String> {
-    let output = Command::new("pacman").arg("--version").output().ok()?;
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pacman = cached_pacman_version()?;
+    Some(format!("Pacman v{} - libalpm v{}", pacman, alpm::version()))
+}
 
-    for line in stdout.lines() {
-        if line.contains("Pacman v")
-            && line.contains("libalpm v")
-            && let Some(version_start) = line.find("Pacman v")
-        {
-            let version_str = &line[version_start..];
-            return Some(version_str.trim().to_string());
-        }
+fn cached_pacman_version() -> Option<String> {
+    let binary = std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join("pacman"))
+        .find(|path| path.is_file())?;
+    let mtime = fs::metadata(&binary)
+        .ok()?
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    let key = format!("{} {}", binary.display(), mtime);
+
+    let cache_path = crate::config::Config::cache_dir()
+        .and_then(|dir| dir.parent().map(|p| p.join("pacman_version")));
+    if let Some(cached) = cache_path.as_ref().and_then(|p| fs::read_to_string(p).ok())
+        && let Some((cached_key, version)) = cached.trim().rsplit_once(' ')
+        && cached_key == key
+    {
+        return Some(version.to_string());
     }
-    None
+
+    let output = Command::new(&binary).arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
+        .split("Pacman v")
+        .nth(1)?
+        .split_whitespace()
+        .next()?
+        .to_string();
+
+    // Under sudo the cache dir is the invoking user's, so leave no root-owned file there
+    let under_sudo = util::is_root() && std::env::var_os("SUDO_USER").is_some();
+    if let Some(path) = cache_path.filter(|_| !under_sudo) {
+        let _ = fs::write(path, format!("{} {}", key, version));
+    }
+    Some(version)
 }
 
 fn check_mirror_sync(mirror_url: &str, debug: bool) -> Option<f64> {
@@ -844,177 +2177,354 @@ fn run_pacman_pty(args: &[&str], filter: bool) -> Result<(), String> {
     Ok(())
 }
 
-fn get_aur_upgradable_count() -> u32 {
-    let mut handle = match Alpm::new("/", "/var/lib/pacman") {
-        Ok(a) => a,
-        Err(_) => return 0,
+/// Installed packages that are not in any sync db, as (name, version)
+fn get_foreign_packages(session: &PackageSession) -> Result<Vec<(String, String)>, SyncDbsError> {
+    let repo_names = session.repo_names()?;
+    Ok(session
+        .local()
+        .ok_or(SyncDbsError::Unreadable)?
+        .iter()
+        .filter(|pkg| !repo_names.contains(&pkg.name))
+        .map(|pkg| (pkg.name.clone(), pkg.version.clone()))
+        .collect())
+}
+
+/// Query the AUR RPC for the given packages in batches, returning
+/// name -> version for every requested name (None if not in the AUR).
+/// Returns None if the endpoint could not be reached.
+fn fetch_aur_versions(
+    names: &[&str],
+    aur: &crate::config::AurConfig,
+    debug: bool,
+) -> Option<HashMap<String, Option<String>>> {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            util::log_error(&format!("Failed to build tokio runtime: {}", e), debug);
+            return None;
+        }
     };
-    let _ = handle.register_syncdb_mut("core", alpm::SigLevel::NONE);
-    let _ = handle.register_syncdb_mut("extra", alpm::SigLevel::NONE);
-    let _ = handle.register_syncdb_mut("multilib", alpm::SigLevel::NONE);
 
-    let mut repo_pkgs: std::collections::HashSet<String> = std::collections::HashSet::new();
-    for db in handle.syncdbs().into_iter() {
-        for pkg in db.pkgs().into_iter() {
-            repo_pkgs.insert(pkg.name().to_string());
+    let handle = raur::Handle::new_with_url(aur.rpc_url.as_str());
+    let timeout = std::time::Duration::from_secs(aur.timeout_secs);
+
+    let result: Result<Vec<raur::Package>, String> = rt.block_on(async {
+        let mut pkgs = Vec::with_capacity(names.len());
+        for chunk in names.chunks(aur.batch_size.max(1)) {
+            match tokio::time::timeout(timeout, handle.raw_info(chunk)).await {
+                Ok(Ok(batch)) => pkgs.extend(batch),
+                Ok(Err(e)) => return Err(e.to_string()),
+                Err(_) => return Err(format!("timed out after {}s", aur.timeout_secs)),
+            }
         }
-    }
+        Ok(pkgs)
+    });
 
-    let foreign: Vec<(String, String)> = handle
-        .localdb()
-        .pkgs()
-        .into_iter()
-        .filter(|pkg| !repo_pkgs.contains(pkg.name()))
-        .map(|pkg| (pkg.name().to_string(), pkg.version().to_string()))
-        .collect();
+    let aur_pkgs = match result {
+        Ok(pkgs) => pkgs,
+        Err(e) => {
+            util::log_error(
+                &format!("AUR RPC request to {} failed: {}", aur.rpc_url, e),
+                debug,
+            );
+            return None;
+        }
+    };
 
+    let mut versions: HashMap<String, Option<String>> =
+        names.iter().map(|n| (n.to_string(), None)).collect();
+    for pkg in aur_pkgs {
+        versions.insert(pkg.name, Some(pkg.version));
+    }
+    Some(versions)
+}
+
+fn get_aur_status(
+    config: &crate::config::Config,
+    session: &PackageSession,
+    debug: bool,
+) -> Option<AurStatus> {
+    let foreign = match get_foreign_packages(session) {
+        Ok(foreign) => foreign,
+        Err(SyncDbsError::InvalidSignature) => return Some(AurStatus::InvalidSignature),
+        Err(SyncDbsError::Unreadable) => return None,
+    };
     if foreign.is_empty() {
-        return 0;
+        return Some(AurStatus::Upgradable(0));
     }
 
+    let aur = &config.aur;
+    let ttl_minutes = config.cache.aur_ttl_minutes;
     let pkg_names: Vec<&str> = foreign.iter().map(|(n, _)| n.as_str()).collect();
-    let Ok(rt) = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-    else {
-        return 0;
-    };
-    let Ok(aur_pkgs): Result<Vec<raur::Package>, _> =
-        rt.block_on(async { raur::Handle::new().info(&pkg_names).await })
-    else {
-        return 0;
+    let cache = AurCache::new();
+
+    let cached = cache
+        .as_ref()
+        .and_then(|c| c.load(ttl_minutes, &aur.rpc_url, &pkg_names));
+    let aur_versions = match cached {
+        Some(cached) => {
+            if debug {
+                eprintln!("  AUR query: SKIP (cache fresh, TTL {}min)", ttl_minutes);
+            }
+            cached
+        }
+        None => {
+            let Some(fetched) = fetch_aur_versions(&pkg_names, aur, debug) else {
+                return Some(AurStatus::Unreachable);
+            };
+            if let Some(ref c) = cache {
+                c.store(&aur.rpc_url, fetched.clone());
+            }
+            fetched
+        }
     };
 
-    let installed: std::collections::HashMap<&str, &str> = foreign
+    let count = foreign
         .iter()
-        .map(|(n, v)| (n.as_str(), v.as_str()))
-        .collect();
+        .filter(|(name, installed)| {
+            aur_versions
+                .get(name)
+                .and_then(|v| v.as_deref())
+                .is_some_and(|aur| alpm::vercmp(aur, installed.as_str()) == Ordering::Greater)
+        })
+        .count();
 
-    aur_pkgs
-        .iter()
-        .filter(|p| {
-            installed
-                .get(p.name.as_str())
-                .map(|iv| alpm::vercmp(p.version.as_str(), iv) == std::cmp::Ordering::Greater)
-                .unwrap_or(false)
+    Some(AurStatus::Upgradable(count as u32))
+}
+
+/// Processes with `lock` open. libalpm keeps its lock file open for as long
+/// as it holds the lock. Only processes we may inspect are seen, so this is
+/// complete for the current user's own locks.
+fn lock_file_holders(lock: &Path) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut pids: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            holds_file(&entry.path(), lock).then_some(pid)
         })
-        .count() as u32
+        .collect();
+    pids.sort();
+    pids
 }
 
-fn run_pacman_sync() -> Result<(), String> {
-    if !util::is_root() {
-        return Err("you cannot perform this operation unless you are root.".to_string());
-    }
+/// Whether the /proc/<pid> process has `path` open
+fn holds_file(proc_dir: &Path, path: &Path) -> bool {
+    fs::read_dir(proc_dir.join("fd")).is_ok_and(|fds| {
+        fds.flatten()
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == path))
+    })
+}
 
-    let mut session =
-        expectrl::spawn("pacman -Sy").map_err(|e| format!("Failed to spawn pacman: {}", e))?;
+/// pacman and the AUR helpers that run it
+const LOCK_HOLDER_NAMES: &[&str] = &["pacman", "yay", "paru", "pikaur", "trizen", "aura"];
 
-    if let Ok((cols, rows)) = crossterm::terminal::size() {
-        let _ = session.get_process_mut().set_window_size(cols, rows);
-    }
+/// Processes running pacman or an AUR helper, or with the lock file open as
+/// libalpm keeps it while it holds the lock. pacfetch itself is left out.
+fn find_lock_holders(lock: &Path) -> Vec<LockHolder> {
+    let own_pid = std::process::id();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
 
-    session.set_expect_timeout(Some(std::time::Duration::from_millis(100)));
+    let mut holders: Vec<LockHolder> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            if pid == own_pid {
+                return None;
+            }
+            let name = fs::read_to_string(entry.path().join("comm")).ok()?;
+            let name = name.trim().to_string();
+            // Other pacfetch instances read the db without taking the lock
+            if name == "pacfetch" {
+                return None;
+            }
+            let holds =
+                LOCK_HOLDER_NAMES.contains(&name.as_str()) || holds_file(&entry.path(), lock);
+            holds.then_some(LockHolder { pid, name })
+        })
+        .collect();
+    holders.sort_by_key(|h| h.pid);
+    holders
+}
 
-    let mut progress = SyncProgress::new();
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.cyan} Syncing databases: {msg}")
-            .unwrap(),
-    );
-    pb.set_message(progress.format());
-    pb.enable_steady_tick(std::time::Duration::from_millis(80));
+fn get_db_lock_status() -> DbLockStatus {
+    db_lock_status(Path::new(DB_LOCK_PATH))
+}
 
-    let mut line_buffer = String::new();
+fn db_lock_status(lock: &Path) -> DbLockStatus {
+    let Ok(meta) = fs::metadata(lock) else {
+        return DbLockStatus::Unlocked;
+    };
+    let age_secs = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .map_or(0, |d| d.as_secs() as i64);
+
+    let holders = find_lock_holders(lock);
+    if holders.is_empty() {
+        DbLockStatus::Stale { age_secs }
+    } else {
+        DbLockStatus::Held { age_secs, holders }
+    }
+}
+
+/// Wait with a spinner while another process holds the db lock, and offer to
+/// remove a stale lock.
+fn wait_for_db_lock(timeout_secs: u64, debug: bool) -> Result<(), String> {
+    let start = Instant::now();
+    let mut spinner: Option<ProgressBar> = None;
 
     loop {
-        match session.is_alive() {
-            Ok(true) => {}
-            Ok(false) => {
-                if !line_buffer.is_empty() {
-                    progress.update_from_line(&line_buffer);
-                    pb.set_message(progress.format());
+        match get_db_lock_status() {
+            DbLockStatus::Unlocked => break,
+            DbLockStatus::Held { holders, .. } => {
+                let names: Vec<String> = holders
+                    .iter()
+                    .map(|h| format!("{} ({})", h.name, h.pid))
+                    .collect();
+                if start.elapsed().as_secs() >= timeout_secs {
+                    if let Some(pb) = spinner {
+                        pb.finish_and_clear();
+                    }
+                    return Err(format!(
+                        "timed out after {}s waiting for the database lock held by {}",
+                        timeout_secs,
+                        names.join(", ")
+                    ));
                 }
-                break;
-            }
-            Err(_) => break,
-        }
-
-        let mut buf = [0u8; 1024];
-        match session.try_read(&mut buf) {
-            Ok(0) => continue,
-            Ok(n) => {
-                let chunk = String::from_utf8_lossy(&buf[..n]);
-
-                for ch in chunk.chars() {
-                    if ch == '\n' || ch == '\r' {
-                        if !line_buffer.is_empty() {
-                            progress.update_from_line(&line_buffer);
-                            pb.set_message(progress.format());
+                let msg = format!(
+                    "Waiting for {} to release the database lock",
+                    names.join(", ")
+                );
+                match &spinner {
+                    Some(pb) => pb.set_message(msg),
+                    None => {
+                        if debug {
+                            eprintln!("Database lock held by {}", names.join(", "));
                         }
-                        line_buffer.clear();
-                    } else {
-                        line_buffer.push(ch);
+                        spinner = Some(util::create_spinner(&msg));
                     }
                 }
+                std::thread::sleep(std::time::Duration::from_millis(500));
             }
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted => {
-                    std::thread::sleep(std::time::Duration::from_millis(10));
+            DbLockStatus::Stale { age_secs } => {
+                if let Some(pb) = spinner.take() {
+                    pb.finish_and_clear();
                 }
-                _ => break,
-            },
+                return remove_stale_lock(age_secs);
+            }
         }
     }
 
-    pb.finish_and_clear();
-
+    if let Some(pb) = spinner {
+        pb.finish_and_clear();
+    }
     Ok(())
 }
 
-// --- Public API ---
+fn remove_stale_lock(age_secs: i64) -> Result<(), String> {
+    use std::io::Write;
+
+    println!(
+        ":: {} exists but no pacman or libalpm process is running.",
+        DB_LOCK_PATH
+    );
+    println!(
+        "   It is {} old and was most likely left behind by an interrupted pacman.",
+        util::normalize_duration(age_secs)
+    );
+    print!(":: Remove the stale lock and continue? [y/N] ");
+    let _ = std::io::stdout().flush();
+
+    let mut input = String::new();
+    let _ = std::io::stdin().read_line(&mut input);
+    if !input.trim().eq_ignore_ascii_case("y") {
+        return Err(format!(
+            "database is locked; remove {} once no package manager is running",
+            DB_LOCK_PATH
+        ));
+    }
 
-pub fn sync_databases() -> Result<(), String> {
-    run_pacman_sync()
+    fs::remove_file(DB_LOCK_PATH).map_err(|e| format!("Failed to remove {}: {}", DB_LOCK_PATH, e))
 }
 
-pub fn yay_upgrade(debug: bool, config: &crate::config::Config) -> Result<(), String> {
-    if !matches!(Command::new("yay").arg("--version").output(), Ok(o) if o.status.success()) {
-        return Err("yay is not installed.".to_string());
+fn run_pacman_sync(debug: bool) -> Result<(), String> {
+    if !util::is_root() {
+        return Err("you cannot perform this operation unless you are root.".to_string());
     }
 
-    // Sync temp databases
     let spinner = if debug {
         None
     } else {
-        Some(util::create_spinner("Gathering stats"))
+        Some(util::create_spinner("Syncing databases"))
     };
-    let stat_ids = config.display.parsed_stats();
-    let mut stats = get_stats(&stat_ids, debug, true, config, spinner.as_ref());
-    let aur_count = get_aur_upgradable_count();
-    if let Some(ref s) = spinner {
+    let result = sync_dbs("/var/lib/pacman", spinner.as_ref(), debug);
+    if let Some(s) = spinner {
         s.finish_and_clear();
     }
+    result.map(|_| ())
+}
+
+// --- Public API ---
+
+pub fn sync_databases(debug: bool) -> Result<(), String> {
+    run_pacman_sync(debug)
+}
+
+/// An AUR helper used for `--aur` (and the `--yay`/`--paru` aliases)
+struct AurHelper<'a> {
+    program: &'a str,
+    upgrade_args: &'static [&'static str],
+}
+
+impl<'a> AurHelper<'a> {
+    fn new(program: &'a str) -> Self {
+        let name = program.rsplit('/').next().unwrap_or(program);
+        let upgrade_args: &'static [&'static str] = match name {
+            "yay" | "paru" | "pikaur" | "trizen" => &["-Syu"],
+            "aura" => &["-Au"],
+            // Custom helpers get only the configured/passed-through args
+            _ => &[],
+        };
+        Self {
+            program,
+            upgrade_args,
+        }
+    }
+
+    fn is_installed(&self) -> bool {
+        if self.program.contains('/') {
+            return std::path::Path::new(self.program).is_file();
+        }
+        std::env::var_os("PATH").is_some_and(|paths| {
+            std::env::split_paths(&paths).any(|p| p.join(self.program).is_file())
+        })
+    }
 
-    stats.total_upgradable += aur_count;
-
-    if debug {
-        crate::ui::display_stats(&stats, config);
-        println!();
-    } else if let Err(e) = crate::ui::display_stats_with_graphics(&stats, config) {
-        eprintln!("error: {}", e);
-        crate::ui::display_stats(&stats, config);
-        println!();
+    fn command(&self, extra_args: &[String]) -> Command {
+        let mut cmd = Command::new(self.program);
+        cmd.args(self.upgrade_args).args(extra_args);
+        cmd
     }
-
-    //hand off to yay
-    use std::os::unix::process::CommandExt;
-    Err(std::process::Command::new("yay").exec().to_string())
 }
 
-pub fn paru_upgrade(debug: bool, config: &crate::config::Config) -> Result<(), String> {
-    if !matches!(Command::new("paru").arg("--version").output(), Ok(o) if o.status.success()) {
-        return Err("paru is not installed.".to_string());
+pub fn aur_helper_upgrade(
+    helper: &str,
+    helper_args: &[String],
+    debug: bool,
+    config: &crate::config::Config,
+) -> Result<(), String> {
+    let helper = AurHelper::new(helper);
+    if !helper.is_installed() {
+        return Err(format!("{} is not installed.", helper.program));
     }
 
     // Sync temp databases
@@ -1023,15 +2533,14 @@ pub fn paru_upgrade(debug: bool, config: &crate::config::Config) -> Result<(), S
     } else {
         Some(util::create_spinner("Gathering stats"))
     };
-    let stat_ids = config.display.parsed_stats();
-    let mut stats = get_stats(&stat_ids, debug, true, config, spinner.as_ref());
-    let aur_count = get_aur_upgradable_count();
+    // AUR updates are always counted before handing off to an AUR helper
+    let mut stat_ids = config.display.parsed_stats();
+    stat_ids.push(StatIdOrTitle::Stat(StatId::AurUpgradable));
+    let stats = get_stats(&stat_ids, debug, true, config, spinner.as_ref());
     if let Some(ref s) = spinner {
         s.finish_and_clear();
     }
 
-    stats.total_upgradable += aur_count;
-
     if debug {
         crate::ui::display_stats(&stats, config);
         println!();
@@ -1041,9 +2550,26 @@ pub fn paru_upgrade(debug: bool, config: &crate::config::Config) -> Result<(), S
         println!();
     }
 
-    //hand off to paru
+    // config args first so pass-through args can override them
+    let mut args = config.aur.helper_args.clone();
+    args.extend_from_slice(helper_args);
+    if debug {
+        eprintln!(
+            "Handing off to: {} {}",
+            helper.program,
+            helper
+                .upgrade_args
+                .iter()
+                .map(|a| a.to_string())
+                .chain(args.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+
+    //hand off to the helper
     use std::os::unix::process::CommandExt;
-    Err(std::process::Command::new("paru").exec().to_string())
+    Err(helper.command(&args).exec().to_string())
 }
 
 pub fn upgrade_system(
@@ -1055,8 +2581,10 @@ pub fn upgrade_system(
         return Err("you cannot perform this operation unless you are root.".to_string());
     }
 
+    wait_for_db_lock(config.upgrade.lock_timeout_secs, debug)?;
+
     if sync_first {
-        run_pacman_sync()?;
+        run_pacman_sync(debug)?;
     }
     let spinner = if debug {
         None
@@ -1084,7 +2612,257 @@ pub fn upgrade_system(
         println!();
     }
 
-    run_pacman_pty(&["-Su"], true)
+    for args in upgrade_steps(config.upgrade.keyring_first) {
+        run_pacman_pty(args, true)?;
+    }
+    run_post_upgrade_hooks(config, debug);
+    Ok(())
+}
+
+/// pacman invocations for the upgrade, in order
+fn upgrade_steps(keyring_first: bool) -> Vec<&'static [&'static str]> {
+    let mut steps: Vec<&'static [&'static str]> = Vec::new();
+    if keyring_first {
+        // A stale keyring fails signature checks for the rest of the upgrade
+        steps.push(&["-S", "--needed", KEYRING_PACKAGE]);
+    }
+    steps.push(&["-Su"]);
+    steps
+}
+
+/// Run the configured `[upgrade] post_hooks` in order. "flatpak" runs
+/// `flatpak update` with the configured binary; anything else is a shell command.
+fn run_post_upgrade_hooks(config: &crate::config::Config, debug: bool) {
+    for hook in &config.upgrade.post_hooks {
+        let mut cmd = if hook == "flatpak" {
+            let mut c = Command::new(&config.flatpak.path);
+            c.arg("update");
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.args(["-c", hook]);
+            c
+        };
+
+        println!();
+        println!(":: Running post-upgrade hook: {}", hook);
+        match cmd.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => util::log_error(
+                &format!("Post-upgrade hook '{}' exited with {}", hook, status),
+                debug,
+            ),
+            Err(e) => util::log_error(
+                &format!("Failed to run post-upgrade hook '{}': {}", hook, e),
+                debug,
+            ),
+        }
+    }
+}
+
+/// Cached output of a custom stat at ~/.cache/pacfetch/custom/<name>.json
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CustomCacheFile {
+    command: String,
+    value: String,
+}
+
+fn custom_cache_path(name: &str) -> Option<PathBuf> {
+    let cache_dir = crate::config::Config::cache_dir()?;
+    let cache_path = cache_dir.parent()?.join("custom");
+    fs::create_dir_all(&cache_path).ok()?;
+    Some(cache_path.join(format!("{}.json", name)))
+}
+
+/// Run a custom stat's command, killing it after `timeout_ms`. Output is
+/// cached for `cache_ttl` minutes, keyed by the command.
+fn get_custom_stat(
+    name: &str,
+    custom: &crate::config::CustomStatConfig,
+    debug: bool,
+) -> CustomOutput {
+    use std::os::unix::process::CommandExt;
+    use std::process::Stdio;
+
+    let cache_path = (custom.cache_ttl > 0)
+        .then(|| custom_cache_path(name))
+        .flatten();
+    if let Some(contents) = cache_path
+        .as_deref()
+        .and_then(|p| read_fresh_cache(p, custom.cache_ttl))
+        && let Ok(cached) = serde_json::from_str::<CustomCacheFile>(&contents)
+        && cached.command == custom.command
+    {
+        return CustomOutput::Value(cached.value);
+    }
+
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(&custom.command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Own process group, so a timeout kills anything the command started
+        .process_group(0)
+        .spawn();
+    let child = match child {
+        Ok(c) => c,
+        Err(e) => {
+            util::log_error(&format!("Failed to run custom.{}: {}", name, e), debug);
+            return CustomOutput::Failed("could not run command".to_string());
+        }
+    };
+
+    let pid = child.id() as i32;
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+    let output = match rx.recv_timeout(std::time::Duration::from_millis(custom.timeout_ms)) {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            util::log_error(&format!("Failed to run custom.{}: {}", name, e), debug);
+            return CustomOutput::Failed("could not run command".to_string());
+        }
+        Err(_) => {
+            unsafe {
+                libc::kill(-pid, libc::SIGKILL);
+            }
+            util::log_error(
+                &format!("custom.{} timed out after {}ms", name, custom.timeout_ms),
+                debug,
+            );
+            return CustomOutput::TimedOut;
+        }
+    };
+
+    if !output.status.success() {
+        util::log_error(
+            &format!(
+                "custom.{} failed: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            debug,
+        );
+        return CustomOutput::Failed(match output.status.code() {
+            Some(code) => format!("exit {}", code),
+            None => "killed".to_string(),
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let value = stdout.trim().lines().next().unwrap_or_default().to_string();
+    if let Some(path) = cache_path {
+        let file = CustomCacheFile {
+            command: custom.command.clone(),
+            value: value.clone(),
+        };
+        if let Ok(json) = serde_json::to_string(&file) {
+            let _ = fs::write(path, json);
+        }
+    }
+    CustomOutput::Value(value)
+}
+
+/// Fills in the stats one collector gathered
+type StatPatch = Box<dyn FnOnce(&mut PacmanStats) + Send>;
+
+type CollectorMsg = (usize, std::time::Duration, StatPatch);
+
+/// Reports one collector's result back to `get_stats`
+struct Reporter {
+    id: usize,
+    tx: mpsc::Sender<CollectorMsg>,
+    start: Instant,
+}
+
+impl Reporter {
+    fn send(self, patch: StatPatch) {
+        let _ = self.tx.send((self.id, self.start.elapsed(), patch));
+    }
+}
+
+struct Collector {
+    name: Cow<'static, str>,
+    /// Stats shown as timed out if this collector misses the budget
+    covers: &'static [StatId],
+    done: bool,
+}
+
+/// Stat collectors running on their own threads
+struct Collectors {
+    jobs: Vec<Collector>,
+    tx: mpsc::Sender<CollectorMsg>,
+    rx: mpsc::Receiver<CollectorMsg>,
+}
+
+impl Collectors {
+    fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            jobs: Vec::new(),
+            tx,
+            rx,
+        }
+    }
+
+    fn add(&mut self, name: impl Into<Cow<'static, str>>, covers: &'static [StatId]) -> Reporter {
+        self.jobs.push(Collector {
+            name: name.into(),
+            covers,
+            done: false,
+        });
+        Reporter {
+            id: self.jobs.len() - 1,
+            tx: self.tx.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    fn spawn<F>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        covers: &'static [StatId],
+        collect: F,
+    ) where
+        F: FnOnce() -> StatPatch + Send + 'static,
+    {
+        let reporter = self.add(name, covers);
+        std::thread::spawn(move || reporter.send(collect()));
+    }
+
+    /// Apply results as they arrive. Collectors still running at the deadline
+    /// are left behind and their stats marked as timed out.
+    fn finish(mut self, stats: &mut PacmanStats, deadline: Option<Instant>, debug: bool) {
+        drop(self.tx);
+
+        while self.jobs.iter().any(|j| !j.done) {
+            let msg = match deadline {
+                Some(deadline) => self
+                    .rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok(),
+                None => self.rx.recv().ok(),
+            };
+            let Some((id, elapsed, patch)) = msg else {
+                break;
+            };
+            patch(stats);
+            self.jobs[id].done = true;
+            stats.collected.extend_from_slice(self.jobs[id].covers);
+            if debug {
+                eprintln!("{}: {:?}", self.jobs[id].name, elapsed);
+            }
+        }
+
+        for job in self.jobs.iter().filter(|j| !j.done) {
+            if debug {
+                eprintln!("{}: TIMED OUT", job.name);
+            }
+            stats.timed_out.extend_from_slice(job.covers);
+        }
+    }
 }
 
 pub fn get_stats(
@@ -1097,130 +2875,276 @@ pub fn get_stats(
     let ttl_minutes = config.cache.ttl_minutes;
 
     let total_start = Instant::now();
+    let budget_ms = config.performance.budget_ms;
+    let deadline =
+        (budget_ms > 0).then(|| total_start + std::time::Duration::from_millis(budget_ms));
+    let config = Arc::new(config.clone());
+    let session = Arc::new(PackageSession::new(debug));
     let mut stats = PacmanStats::default();
-
-    if needs_upgrade_stats(requested) {
-        let start = Instant::now();
-        let upgrade_stats = if fresh_sync {
-            if debug {
-                eprintln!("Using cached database (TTL {}min)", ttl_minutes);
+    let mut collectors = Collectors::new();
+
+    // Security advisories and the served mirror depend on the upgrade
+    // stats, so they run after it on the same thread
+    let upgrade = needs_upgrade_stats(requested).then(|| {
+        collectors.add(
+            "Upgrade sizes + count",
+            &[
+                StatId::Upgradable,
+                StatId::DownloadSize,
+                StatId::InstalledSize,
+                StatId::NetUpgradeSize,
+                StatId::Keyring,
+            ],
+        )
+    });
+    let security = needs_security_stats(requested)
+        .then(|| collectors.add("Vulnerable packages", &[StatId::VulnerablePackages]));
+    let mirror =
+        needs_mirror_url(requested).then(|| collectors.add("Mirror URL", &[StatId::MirrorUrl]));
+    let mirror_health = (needs_mirror_url(requested) && needs_mirror_health(requested))
+        .then(|| collectors.add("Mirror sync age", &[StatId::MirrorHealth]));
+    if debug {
+        for (name, needed) in [
+            ("Upgrade sizes", upgrade.is_some()),
+            ("Vulnerable packages", security.is_some()),
+            ("Mirror URL", mirror.is_some()),
+            ("Mirror sync age", mirror_health.is_some()),
+        ] {
+            if !needed {
+                eprintln!("{}: SKIP", name);
             }
-            calculate_upgrade_stats_with_sync(spinner, debug, ttl_minutes)
-        } else {
-            calculate_upgrade_stats("/var/lib/pacman", debug)
-        };
-        stats.total_upgradable = upgrade_stats.package_count;
-        stats.download_size_mb = upgrade_stats.download_size_mb;
-        stats.total_installed_size_mb = upgrade_stats.installed_size_mb;
-        stats.net_upgrade_size_mb = upgrade_stats.net_upgrade_size_mb;
-        if debug {
-            eprintln!("Upgrade sizes + count: {:?}", start.elapsed());
         }
-    } else if debug {
-        eprintln!("Upgrade sizes: SKIP");
     }
 
-    if needs_orphan_stats(requested) {
-        let start = Instant::now();
-        let (orphaned_count, orphaned_size) = get_orphaned_packages(debug);
-        stats.orphaned_packages = orphaned_count;
-        stats.orphaned_size_mb = orphaned_size;
-        if debug {
-            eprintln!("Orphaned packages: {:?}", start.elapsed());
+    let want_keyring = needs_keyring_stat(requested);
+    let chain_config = Arc::clone(&config);
+    let chain_spinner = spinner.cloned();
+    let chain_session = Arc::clone(&session);
+    std::thread::spawn(move || {
+        let mut pending_versions = HashMap::new();
+        let mut served_mirror = None;
+
+        if let Some(reporter) = upgrade {
+            let upgrade_stats = if fresh_sync {
+                if debug {
+                    eprintln!("Using cached database (TTL {}min)", ttl_minutes);
+                }
+                calculate_upgrade_stats_with_sync(chain_spinner.as_ref(), debug, ttl_minutes)
+            } else {
+                calculate_upgrade_stats("/var/lib/pacman", debug)
+            };
+            pending_versions = upgrade_stats.pending_versions.clone();
+            served_mirror = upgrade_stats.mirror_url.clone();
+            let source = upgrade_stats.package_count.map(|_| {
+                let (database, sync_dir) = if fresh_sync {
+                    (DbSource::Temp, DbCache::sync_dir_path())
+                } else {
+                    (DbSource::Local, Some(PathBuf::from("/var/lib/pacman/sync")))
+                };
+                DataSource {
+                    database,
+                    db_age_secs: sync_dir.as_deref().and_then(sync_db_age),
+                }
+            });
+            reporter.send(Box::new(move |stats| {
+                stats.total_upgradable = upgrade_stats.package_count;
+                stats.source = source;
+                stats.download_size_bytes = upgrade_stats.download_size_bytes;
+                stats.installed_size_bytes = upgrade_stats.installed_size_bytes;
+                stats.net_upgrade_size_bytes = upgrade_stats.net_upgrade_size_bytes;
+                if want_keyring {
+                    stats.keyring = upgrade_stats.keyring_version.map(|installed| {
+                        keyring_status(installed, &upgrade_stats.pending_versions)
+                    });
+                }
+                stats.invalid_repos = upgrade_stats.invalid_repos;
+            }));
         }
-    } else if debug {
-        eprintln!("Orphaned packages: SKIP");
-    }
 
-    let sync_handle = if needs_mirror_url(requested) {
-        let start = Instant::now();
-        stats.mirror_url = get_mirror_url();
-        if debug {
-            eprintln!("Mirror URL: {:?}", start.elapsed());
+        if let Some(reporter) = security {
+            let vulnerable =
+                get_vulnerable_packages(&chain_config, &pending_versions, &chain_session, debug);
+            reporter.send(Box::new(move |stats| {
+                stats.vulnerable_packages = vulnerable
+            }));
         }
 
-        if needs_mirror_health(requested) {
-            let sync_start = Instant::now();
-            let mirror_url_clone = stats.mirror_url.clone();
-            let handle = std::thread::spawn(move || {
-                mirror_url_clone
+        if let Some(reporter) = mirror {
+            // Prefer the mirror that actually served the temp sync
+            let mirror_url = served_mirror.or_else(get_mirror_url);
+            let url = mirror_url.clone();
+            reporter.send(Box::new(move |stats| stats.mirror_url = url));
+
+            if let Some(reporter) = mirror_health {
+                let age = mirror_url
                     .as_ref()
-                    .and_then(|url| check_mirror_sync(url, debug))
-            });
-            Some((handle, sync_start))
-        } else {
-            if debug {
-                eprintln!("Mirror sync age: SKIP");
+                    .and_then(|url| check_mirror_sync(url, debug));
+                reporter.send(Box::new(move |stats| stats.mirror_sync_age_hours = age));
             }
-            None
-        }
-    } else {
-        if debug {
-            eprintln!("Mirror URL: SKIP");
-            eprintln!("Mirror sync age: SKIP");
         }
-        None
-    };
+    });
 
-    if requested
-        .iter()
-        .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::Installed)))
-    {
-        let start = Instant::now();
-        stats.total_installed = get_installed_count();
-        if debug {
-            eprintln!("Installed count: {:?}", start.elapsed());
-        }
+    if needs_aur_stats(requested) {
+        let config = Arc::clone(&config);
+        let session = Arc::clone(&session);
+        collectors.spawn("AUR upgradable", &[StatId::AurUpgradable], move || {
+            let status = get_aur_status(&config, &session, debug);
+            Box::new(move |stats| stats.aur_upgradable = status)
+        });
+    } else if debug {
+        eprintln!("AUR upgradable: SKIP");
+    }
+
+    if needs_flatpak_stats(requested, config.flatpak.include_in_upgradable) {
+        let config = Arc::clone(&config);
+        collectors.spawn(
+            "Flatpak upgradable",
+            &[StatId::FlatpakUpgradable],
+            move || {
+                let updates = get_flatpak_updates(&config.flatpak.path, debug);
+                Box::new(move |stats| stats.flatpak_upgradable = updates)
+            },
+        );
+    } else if debug {
+        eprintln!("Flatpak upgradable: SKIP");
+    }
+
+    if needs_recent_packages(requested) {
+        let config = Arc::clone(&config);
+        collectors.spawn("Recent packages", &[StatId::RecentPackages], move || {
+            let recent = get_recent_packages(&config.recent_packages, debug);
+            Box::new(move |stats| stats.recent_packages = recent)
+        });
+    } else if debug {
+        eprintln!("Recent packages: SKIP");
+    }
+
+    if needs_db_lock_stat(requested) {
+        collectors.spawn("Database lock", &[StatId::DbLock], move || {
+            let lock = get_db_lock_status();
+            Box::new(move |stats| stats.db_lock = Some(lock))
+        });
+    } else if debug {
+        eprintln!("Database lock: SKIP");
+    }
+
+    if needs_largest_packages(requested) {
+        let config = Arc::clone(&config);
+        let session = Arc::clone(&session);
+        collectors.spawn("Largest packages", &[StatId::LargestPackages], move || {
+            let largest = get_largest_packages(&config.largest_packages, &session);
+            Box::new(move |stats| stats.largest_packages = largest)
+        });
+    } else if debug {
+        eprintln!("Largest packages: SKIP");
+    }
+
+    if needs_orphan_stats(requested) {
+        let session = Arc::clone(&session);
+        collectors.spawn(
+            "Orphaned packages",
+            &[StatId::OrphanedPackages],
+            move || {
+                let (count, size) = get_orphaned_packages(&session);
+                Box::new(move |stats| {
+                    stats.orphaned_packages = count;
+                    stats.orphaned_size_bytes = size;
+                })
+            },
+        );
+    } else if debug {
+        eprintln!("Orphaned packages: SKIP");
+    }
+
+    if needs_installed_stats(requested) {
+        let session = Arc::clone(&session);
+        collectors.spawn(
+            "Installed count",
+            &[
+                StatId::Installed,
+                StatId::ExplicitInstalled,
+                StatId::DependencyInstalled,
+            ],
+            move || {
+                let counts = get_installed_counts(&session);
+                Box::new(move |stats| {
+                    if let Some((total, explicit)) = counts {
+                        stats.total_installed = Some(total);
+                        stats.explicit_installed = Some(explicit);
+                        stats.dependency_installed = Some(total - explicit);
+                    }
+                })
+            },
+        );
     }
 
     if requested
         .iter()
         .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::LastUpdate)))
     {
-        let start = Instant::now();
-        stats.days_since_last_update = get_seconds_since_update();
-        if debug {
-            eprintln!("Last update time: {:?}", start.elapsed());
-        }
+        collectors.spawn("Last update time", &[StatId::LastUpdate], move || {
+            let time = get_last_update_time();
+            Box::new(move |stats| {
+                stats.last_update_time = time;
+                stats.days_since_last_update =
+                    time.map(|t| Local::now().signed_duration_since(t).num_seconds().max(0));
+            })
+        });
     }
 
     if requested
         .iter()
         .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::CacheSize)))
     {
-        let start = Instant::now();
-        stats.cache_size_mb = get_cache_size();
-        if debug {
-            eprintln!("Cache size: {:?}", start.elapsed());
-        }
+        collectors.spawn("Cache size", &[StatId::CacheSize], move || {
+            let size = get_cache_size();
+            Box::new(move |stats| stats.cache_size_bytes = size)
+        });
     }
 
     if needs_disk_stat(requested) {
-        let start = Instant::now();
-        if let Some((used, total)) = get_disk_usage(&config.disk.path) {
-            stats.disk_used_bytes = Some(used);
-            stats.disk_total_bytes = Some(total);
-        }
-        if debug {
-            eprintln!("Disk usage: {:?}", start.elapsed());
-        }
+        let config = Arc::clone(&config);
+        collectors.spawn("Disk usage", &[StatId::Disk], move || {
+            let usage = get_disk_usage(&config.disk.path);
+            Box::new(move |stats| {
+                if let Some((used, total)) = usage {
+                    stats.disk_used_bytes = Some(used);
+                    stats.disk_total_bytes = Some(total);
+                }
+            })
+        });
     } else if debug {
         eprintln!("Disk usage: SKIP");
     }
 
-    let start = Instant::now();
-    stats.pacman_version = get_pacman_version();
-    if debug {
-        eprintln!("Pacman version: {:?}", start.elapsed());
-    }
+    let custom_names = requested_custom_stats(requested);
+    for name in &custom_names {
+        let Some(custom) = config.display.custom.get(*name).cloned() else {
+            crate::log::warn(&format!("Custom stat '{}' not found in config", name));
+            continue;
+        };
+        let name = name.to_string();
+        collectors.spawn(format!("Custom stat '{}'", name), &[], move || {
+            let output = get_custom_stat(&name, &custom, debug);
+            Box::new(move |stats| {
+                stats.custom.insert(name, output);
+            })
+        });
+    }
+
+    collectors.spawn("Pacman version", &[], move || {
+        let version = get_pacman_version();
+        Box::new(move |stats| stats.pacman_version = version)
+    });
 
-    if let Some((handle, sync_start)) = sync_handle {
-        if let Some(pb) = spinner {
-            pb.set_message("Checking mirror last sync");
-        }
-        stats.mirror_sync_age_hours = handle.join().ok().flatten();
-        if debug {
-            eprintln!("Mirror sync age: {:?}", sync_start.elapsed());
+    collectors.finish(&mut stats, deadline, debug);
+
+    // Custom stats still running at the deadline
+    for name in custom_names {
+        if config.display.custom.contains_key(name) && !stats.custom.contains_key(name) {
+            stats
+                .custom
+                .insert(name.to_string(), CustomOutput::TimedOut);
         }
     }
 
@@ -1230,3 +3154,564 @@ pub fn get_stats(
 
     stats
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// Minimal AUR RPC stand-in: answers `requests` POSTs, reporting every
+    /// requested package at version 2.0-1, and returns the batch sizes seen
+    fn spawn_aur_stub(requests: usize) -> (String, std::thread::JoinHandle<Vec<usize>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rpc/", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            let mut batches = Vec::new();
+            for _ in 0..requests {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let body = loop {
+                    let n = stream.read(&mut chunk).unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(split) = text.find("\r\n\r\n") {
+                        let len = text
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(String::from)
+                            })
+                            .and_then(|v| v.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if buf.len() >= split + 4 + len {
+                            break text[split + 4..].to_string();
+                        }
+                    }
+                };
+
+                let names: Vec<String> = body
+                    .split('&')
+                    .filter_map(|kv| kv.strip_prefix("arg%5B%5D="))
+                    .map(String::from)
+                    .collect();
+                batches.push(names.len());
+
+                let results: Vec<String> = names
+                    .iter()
+                    .map(|n| {
+                        format!(
+                            r#"{{"ID":1,"Name":"{}","PackageBaseID":1,"PackageBase":"{}","Version":"2.0-1","NumVotes":0,"Popularity":0.0,"FirstSubmitted":0,"LastModified":0}}"#,
+                            n, n
+                        )
+                    })
+                    .collect();
+                let json = format!(
+                    r#"{{"version":5,"type":"multiinfo","resultcount":{},"results":[{}]}}"#,
+                    results.len(),
+                    results.join(",")
+                );
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    json.len(),
+                    json
+                );
+            }
+            batches
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn test_fetch_aur_versions_batches_requests() {
+        let (url, stub) = spawn_aur_stub(2);
+        let aur = crate::config::AurConfig {
+            rpc_url: url,
+            timeout_secs: 5,
+            batch_size: 2,
+            ..Default::default()
+        };
+
+        let versions = fetch_aur_versions(&["foo", "bar", "baz"], &aur, false).unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions["baz"].as_deref(), Some("2.0-1"));
+        assert_eq!(stub.join().unwrap(), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_flatpak_updates_from_stand_in_binary() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("pacfetch-flatpak-{}", std::process::id()));
+        fs::write(
+            &path,
+            "#!/bin/sh\ncase \"$3\" in\n  --system) printf 'org.gimp.GIMP\\norg.mozilla.firefox\\n' ;;\n  --user) echo com.spotify.Client ;;\nesac\n",
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let updates = get_flatpak_updates(path.to_str().unwrap(), false).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(updates.system, vec!["org.gimp.GIMP", "org.mozilla.firefox"]);
+        assert_eq!(updates.user, vec!["com.spotify.Client"]);
+        assert_eq!(updates.count(), 3);
+
+        // A failing remote is an error, not zero updates
+        let path =
+            std::env::temp_dir().join(format!("pacfetch-flatpak-fail-{}", std::process::id()));
+        fs::write(
+            &path,
+            "#!/bin/sh
+echo 'error: remote unreachable' >&2
+exit 1
+",
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let updates = get_flatpak_updates(path.to_str().unwrap(), false);
+        let _ = fs::remove_file(&path);
+        assert!(updates.is_none());
+    }
+
+    #[test]
+    fn test_match_advisories() {
+        let group = |pkg: &str, status: &str, severity: &str, fixed: Option<&str>| AdvisoryGroup {
+            packages: vec![pkg.to_string()],
+            status: status.to_string(),
+            severity: severity.to_string(),
+            fixed: fixed.map(String::from),
+        };
+        let groups = vec![
+            group("openssl", "Fixed", "High", Some("3.0.2-1")),
+            group("curl", "Vulnerable", "Medium", None),
+            group("curl", "Fixed", "Critical", Some("8.1.0-1")),
+            group("zlib", "Fixed", "Low", Some("1.3.0-1")),
+            group("vim", "Not affected", "Low", None),
+            // Older than the tracker's affected version still counts
+            group("sudo", "Fixed", "High", Some("1.9.15-1")),
+            group("glibc", "Testing", "Medium", Some("2.39-2")),
+        ];
+        let installed: HashMap<String, String> = [
+            ("openssl", "3.0.1-1"),
+            ("curl", "8.0.0-1"),
+            ("zlib", "1.3.0-1"),
+            ("vim", "9.0-1"),
+            ("sudo", "1.8.0-1"),
+            ("glibc", "2.39-1"),
+        ]
+        .iter()
+        .map(|(n, v)| (n.to_string(), v.to_string()))
+        .collect();
+        let pending: HashMap<String, String> = [("openssl", "3.0.2-1"), ("curl", "8.1.0-1")]
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+
+        let vuln = match_advisories(&groups, &installed, &pending);
+        let names: Vec<&str> = vuln.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["curl", "openssl", "sudo", "glibc"]);
+        assert_eq!(vuln.packages[0].severity, "Critical");
+        assert_eq!(vuln.fixed_by_upgrade(), 1);
+        assert_eq!(vuln.no_fix(), 1);
+        assert_eq!(vuln.fix_pending(), 2);
+    }
+
+    #[test]
+    fn test_match_advisories_takes_highest_fix() {
+        let group = |fixed: &str| AdvisoryGroup {
+            packages: vec!["openssl".to_string()],
+            status: "Fixed".to_string(),
+            severity: "High".to_string(),
+            fixed: Some(fixed.to_string()),
+        };
+        let groups = vec![group("3.0.2-1"), group("3.0.5-1")];
+        let installed = HashMap::from([("openssl".to_string(), "3.0.1-1".to_string())]);
+
+        // The upgrade fixes the first AVG but not the second
+        let pending = HashMap::from([("openssl".to_string(), "3.0.3-1".to_string())]);
+        let vuln = match_advisories(&groups, &installed, &pending);
+        assert_eq!(vuln.packages[0].fixed_version.as_deref(), Some("3.0.5-1"));
+        assert_eq!(vuln.fixed_by_upgrade(), 0);
+        assert_eq!(vuln.fix_pending(), 1);
+
+        let pending = HashMap::from([("openssl".to_string(), "3.0.5-1".to_string())]);
+        let vuln = match_advisories(&groups, &installed, &pending);
+        assert_eq!(vuln.fixed_by_upgrade(), 1);
+    }
+
+    #[test]
+    fn test_rank_largest_with_exclusive_deps() {
+        let pkg = |name: &str, size: i64, is_dep: bool, required_by: &[&str]| LocalPkg {
+            name: name.to_string(),
+            version: "1.0-1".to_string(),
+            size,
+            is_dep,
+            required_by: required_by.iter().map(|s| s.to_string()).collect(),
+            optional_for: false,
+        };
+        let pkgs = vec![
+            pkg("app", 10, false, &[]),
+            pkg("app-data", 50, true, &["app"]),
+            pkg("app-data-fonts", 5, true, &["app-data"]),
+            pkg("tool", 40, false, &[]),
+            pkg("shared-lib", 30, true, &["app", "tool"]),
+        ];
+
+        let plain = rank_largest(&pkgs, 2, false);
+        let names: Vec<&str> = plain.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["app-data", "tool"]);
+
+        let grouped = rank_largest(&pkgs, 3, true);
+        assert_eq!(grouped[0].name, "app");
+        assert_eq!(grouped[0].size_bytes, 65);
+        assert_eq!(grouped[0].exclusive_deps, 2);
+        let names: Vec<&str> = grouped.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["app", "tool", "shared-lib"]);
+    }
+
+    #[test]
+    fn test_flatpak_missing_binary() {
+        assert!(get_flatpak_updates("/nonexistent/flatpak", false).is_none());
+    }
+
+    #[test]
+    fn test_fetch_aur_versions_unreachable() {
+        // Bind then drop to get a port with nothing listening
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let aur = crate::config::AurConfig {
+            rpc_url: format!("http://127.0.0.1:{}/rpc/", port),
+            timeout_secs: 2,
+            batch_size: 100,
+            ..Default::default()
+        };
+
+        assert!(fetch_aur_versions(&["foo"], &aur, false).is_none());
+    }
+
+    fn scan_recent(
+        log: &str,
+        now: DateTime<FixedOffset>,
+        count: usize,
+        days: Option<u32>,
+    ) -> Vec<RecentPackage> {
+        let mut scan = RecentScan::new(now, count, days);
+        for line in log.lines().rev() {
+            if !scan.push(line) {
+                break;
+            }
+        }
+        scan.finish()
+    }
+
+    #[test]
+    fn test_parse_recent_packages() {
+        let log = "\
+[2026-10-10T09:00:00+0000] [PACMAN] Running 'pacman -Syu'
+[2026-10-10T09:00:05+0000] [ALPM] upgraded linux (6.11.1-1 -> 6.11.2-1)
+[2026-10-10T09:00:06+0000] [ALPM] installed btop (1.4.0-1)
+[2026-10-12T09:00:00+0000] [ALPM] installed htop (3.3.0-1)
+[2026-10-13T09:00:00+0000] [ALPM] removed htop (3.3.0-1)
+[2026-10-14T09:00:00+0000] [ALPM] upgraded mesa (24.2.3-1 -> 24.2.4-1)
+[2026-10-15T09:00:00+0000] [ALPM] downgraded linux (6.11.2-1 -> 6.11.1-1)
+[2026-10-15T09:30:00+0000] [ALPM] reinstalled btop (1.4.0-1)
+";
+        let now = DateTime::parse_from_rfc3339("2026-10-15T10:00:00+00:00").unwrap();
+
+        let recent = scan_recent(log, now, 10, None);
+        let names: Vec<&str> = recent.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["btop", "linux", "mesa"]);
+        assert_eq!(recent[0].change, PackageChange::Reinstalled);
+        assert_eq!(recent[1].change, PackageChange::Downgraded);
+        assert_eq!(recent[1].version, "6.11.1-1");
+        assert_eq!(recent[1].seconds_ago, 3600);
+
+        let window = scan_recent(log, now, 10, Some(2));
+        assert_eq!(window.len(), 3);
+        assert_eq!(scan_recent(log, now, 1, None).len(), 1);
+
+        // Lines longer than a chunk and a log without a trailing newline
+        let path = std::env::temp_dir().join(format!("pacfetch-log-{}", std::process::id()));
+        let padding = "x".repeat(LOG_CHUNK_BYTES as usize);
+        fs::write(&path, format!("{}\n{}{}", padding, log, padding)).unwrap();
+        let mut lines = Vec::new();
+        read_lines_rev(&path, |line| {
+            lines.push(line.to_string());
+            true
+        })
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+        let expected: Vec<String> = format!("{}\n{}{}", padding, log, padding)
+            .lines()
+            .rev()
+            .map(String::from)
+            .collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_parse_sync_repos_siglevel() {
+        use alpm::SigLevel as S;
+
+        let mirrorlist =
+            std::env::temp_dir().join(format!("pacfetch-mirrorlist-{}", std::process::id()));
+        fs::write(
+            &mirrorlist,
+            "# Worldwide\n#Server = https://off.example/$repo/os/$arch\nServer = https://one.example/$repo/os/$arch\n",
+        )
+        .unwrap();
+        let conf = format!(
+            "\
+[options]
+Architecture = x86_64
+SigLevel = Required DatabaseOptional
+LocalFileSigLevel = Optional
+
+[core]
+Include = {}
+
+[extra]
+Server = https://mirror.example/$repo/os/$arch
+
+[custom]
+SigLevel = Optional TrustAll # local repo
+Server = file:///srv/repo
+",
+            mirrorlist.display()
+        );
+        let repos = parse_sync_repos(&conf);
+        let _ = fs::remove_file(&mirrorlist);
+        let names: Vec<&str> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["core", "extra", "custom"]);
+        assert_eq!(
+            repos[0].servers,
+            vec![Mirror {
+                url: "https://one.example/core/os/x86_64".to_string(),
+                base: "https://one.example".to_string(),
+            }]
+        );
+        assert_eq!(
+            repos[1].servers,
+            vec![Mirror {
+                url: "https://mirror.example/extra/os/x86_64".to_string(),
+                base: "https://mirror.example".to_string(),
+            }]
+        );
+        assert_eq!(
+            repos[0].siglevel,
+            S::PACKAGE | S::DATABASE | S::DATABASE_OPTIONAL
+        );
+        assert!(
+            repos[2]
+                .siglevel
+                .contains(S::PACKAGE_OPTIONAL | S::DATABASE_UNKNOWN_OK)
+        );
+        assert_eq!(repos[2].servers[0].url, "file:///srv/repo");
+    }
+
+    #[test]
+    fn test_sync_progress_from_download_events() {
+        use alpm::{DownloadEvent, DownloadEventCompleted, DownloadEventProgress, DownloadResult};
+
+        let mut progress = SyncProgress::new(vec!["core".to_string(), "extra".to_string()]);
+        progress.update(
+            "core.db",
+            DownloadEvent::Progress(DownloadEventProgress {
+                downloaded: 50,
+                total: 200,
+            }),
+            false,
+        );
+        progress.update(
+            "extra.db",
+            DownloadEvent::Completed(DownloadEventCompleted {
+                total: 0,
+                result: DownloadResult::Failed,
+            }),
+            false,
+        );
+        progress.update(
+            "extra.db.sig",
+            DownloadEvent::Completed(DownloadEventCompleted {
+                total: 0,
+                result: DownloadResult::Success,
+            }),
+            false,
+        );
+
+        assert!(progress.format().starts_with("core 25%"));
+        assert!(progress.format().ends_with("| extra ✗"));
+        assert_eq!(progress.failed(), vec!["extra"]);
+    }
+
+    #[test]
+    fn test_stale_cache_lock_is_removed() {
+        let root = std::env::temp_dir().join(format!("pacfetch-lock-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let cache = DbCache { path: root.clone() };
+        let lock = root.join("db.lck");
+
+        // An open lock file belongs to a running sync
+        let held = fs::File::create(&lock).unwrap();
+        cache.remove_stale_lock(false);
+        assert!(lock.exists());
+
+        drop(held);
+        cache.remove_stale_lock(false);
+        assert!(!lock.exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_keyring_status() {
+        let pending: HashMap<String, String> =
+            [(KEYRING_PACKAGE.to_string(), "20240520-1".to_string())].into();
+        let status = keyring_status("20240313-1".to_string(), &pending);
+        assert_eq!(status.available.as_deref(), Some("20240520-1"));
+
+        let status = keyring_status("20240520-1".to_string(), &pending);
+        assert_eq!(status.available, None);
+        let status = keyring_status("20240313-1".to_string(), &HashMap::new());
+        assert_eq!(status.available, None);
+    }
+
+    #[test]
+    fn test_upgrade_steps_keyring_first() {
+        assert_eq!(
+            upgrade_steps(true),
+            vec![&["-S", "--needed", KEYRING_PACKAGE][..], &["-Su"][..]]
+        );
+        assert_eq!(upgrade_steps(false), vec![&["-Su"][..]]);
+    }
+
+    #[test]
+    fn test_db_lock_status() {
+        let root = std::env::temp_dir().join(format!("pacfetch-dblock-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let lock = root.join("db.lck");
+        assert_eq!(db_lock_status(&lock), DbLockStatus::Unlocked);
+
+        fs::write(&lock, b"").unwrap();
+        assert!(matches!(db_lock_status(&lock), DbLockStatus::Stale { .. }));
+
+        // A process with the lock file open holds it
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .stdin(fs::File::open(&lock).unwrap())
+            .spawn()
+            .unwrap();
+        let status = db_lock_status(&lock);
+        let _ = child.kill();
+        let _ = child.wait();
+        match status {
+            DbLockStatus::Held { holders, .. } => {
+                assert!(holders.iter().any(|h| h.pid == child.id()));
+            }
+            other => panic!("expected held lock, got {:?}", other),
+        }
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_sync_failover_to_file_mirror() {
+        let root = std::env::temp_dir().join(format!("pacfetch-mirrors-{}", std::process::id()));
+        let good = root.join("good");
+        fs::create_dir_all(&good).unwrap();
+        fs::write(good.join("core.db"), b"db").unwrap();
+
+        let mirror = |dir: &std::path::Path| Mirror {
+            url: format!("file://{}", dir.display()),
+            base: format!("file://{}", dir.display()),
+        };
+        let repos = vec![
+            SyncRepo {
+                name: "core".to_string(),
+                siglevel: alpm::SigLevel::NONE,
+                servers: vec![mirror(&root.join("down")), mirror(&good)],
+            },
+            SyncRepo {
+                name: "extra".to_string(),
+                siglevel: alpm::SigLevel::NONE,
+                servers: vec![mirror(&root.join("down"))],
+            },
+        ];
+
+        let dbpath = root.join("db");
+        fs::create_dir_all(&dbpath).unwrap();
+        let dbpath = dbpath.to_str().unwrap();
+
+        let result = sync_repos(dbpath, &repos, None, false);
+        assert_eq!(
+            result.unwrap_err(),
+            "Failed to sync extra: no working mirror"
+        );
+
+        let served = sync_repos(dbpath, &repos[..1], None, false).unwrap();
+        assert_eq!(served, vec![("core".to_string(), mirror(&good).base)]);
+        assert_eq!(fs::read(root.join("db/sync/core.db")).unwrap(), b"db");
+
+        let cache = DbCache { path: root.clone() };
+        cache.set_served_mirrors(&[
+            ("core".to_string(), "https://b.example".to_string()),
+            ("extra".to_string(), "https://a.example".to_string()),
+            ("multilib".to_string(), "https://a.example".to_string()),
+        ]);
+        assert_eq!(cache.served_mirror().as_deref(), Some("https://a.example"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_collectors_mark_slow_stats_timed_out() {
+        let mut collectors = Collectors::new();
+        collectors.spawn("Cache size", &[StatId::CacheSize], || {
+            Box::new(|stats| stats.cache_size_bytes = Some(1024))
+        });
+        collectors.spawn("Orphaned packages", &[StatId::OrphanedPackages], || {
+            std::thread::sleep(std::time::Duration::from_secs(2));
+            Box::new(|stats| stats.orphaned_packages = Some(3))
+        });
+
+        let mut stats = PacmanStats::default();
+        let deadline = Instant::now() + std::time::Duration::from_millis(200);
+        collectors.finish(&mut stats, Some(deadline), false);
+
+        assert_eq!(stats.cache_size_bytes, Some(1024));
+        assert_eq!(stats.orphaned_packages, None);
+        assert_eq!(stats.timed_out, vec![StatId::OrphanedPackages]);
+    }
+
+    #[test]
+    fn test_custom_stat_output_and_timeout() {
+        let custom = |command: &str| crate::config::CustomStatConfig {
+            command: command.to_string(),
+            label: String::new(),
+            timeout_ms: 200,
+            cache_ttl: 0,
+        };
+
+        assert_eq!(
+            get_custom_stat("vpn", &custom("echo up; echo ignored"), false),
+            CustomOutput::Value("up".to_string())
+        );
+        assert_eq!(
+            get_custom_stat("vpn", &custom("exit 3"), false),
+            CustomOutput::Failed("exit 3".to_string())
+        );
+        assert_eq!(
+            get_custom_stat("vpn", &custom("sleep 5"), false),
+            CustomOutput::TimedOut
+        );
+    }
+}
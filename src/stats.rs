@@ -1,19 +1,32 @@
 use serde::Deserialize;
 
-use crate::pacman::PacmanStats;
+use crate::config::{Config, SizeConfig, TimestampFormat};
+use crate::locale::Locale;
+use crate::pacman::{AurStatus, CustomOutput, DbLockStatus, PackageChange, PacmanStats};
 use crate::util;
+use chrono::Local;
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum StatId {
     Title,
     Installed,
+    ExplicitInstalled,
+    DependencyInstalled,
     Upgradable,
+    AurUpgradable,
+    FlatpakUpgradable,
     LastUpdate,
     DownloadSize,
     InstalledSize,
     NetUpgradeSize,
     OrphanedPackages,
+    VulnerablePackages,
+    LargestPackages,
+    RecentPackages,
+    Keyring,
+    DbLock,
     CacheSize,
     MirrorUrl,
     MirrorHealth,
@@ -22,18 +35,103 @@ pub enum StatId {
 
 pub const ALL_STAT_IDS: &[StatId] = &[
     StatId::Installed,
+    StatId::ExplicitInstalled,
+    StatId::DependencyInstalled,
     StatId::Upgradable,
+    StatId::AurUpgradable,
+    StatId::FlatpakUpgradable,
     StatId::LastUpdate,
     StatId::DownloadSize,
     StatId::InstalledSize,
     StatId::NetUpgradeSize,
     StatId::OrphanedPackages,
+    StatId::VulnerablePackages,
+    StatId::LargestPackages,
+    StatId::RecentPackages,
+    StatId::Keyring,
+    StatId::DbLock,
     StatId::CacheSize,
     StatId::MirrorUrl,
     StatId::MirrorHealth,
     StatId::Disk,
 ];
 
+/// What a stat's threshold values measure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Count,
+    /// Seconds; thresholds like `14d` or `24h`
+    Duration,
+    /// Bytes; thresholds like `5GiB` or `500MB`
+    Size,
+    Percent,
+}
+
+impl MetricKind {
+    /// Parse a threshold such as `100`, `14d`, `5GiB` or `90%` into the
+    /// metric's base unit
+    pub fn parse_threshold(&self, s: &str) -> Result<f64, String> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+            .unwrap_or(s.len());
+        let (number, unit) = (&s[..split], s[split..].trim());
+        let number: f64 = number
+            .parse()
+            .map_err(|_| format!("invalid threshold: {}", s))?;
+
+        let scale = match (self, unit) {
+            (MetricKind::Count, "") => 1.0,
+            (MetricKind::Percent, "" | "%") => 1.0,
+            (MetricKind::Duration, "" | "s") => 1.0,
+            (MetricKind::Duration, "m") => 60.0,
+            (MetricKind::Duration, "h") => 3600.0,
+            (MetricKind::Duration, "d") => 86400.0,
+            (MetricKind::Duration, "w") => 604800.0,
+            (MetricKind::Size, "" | "B") => 1.0,
+            (MetricKind::Size, "KiB") => 1024.0,
+            (MetricKind::Size, "MiB") => 1048576.0,
+            (MetricKind::Size, "GiB") => 1073741824.0,
+            (MetricKind::Size, "TiB") => 1099511627776.0,
+            (MetricKind::Size, "kB" | "KB") => 1e3,
+            (MetricKind::Size, "MB") => 1e6,
+            (MetricKind::Size, "GB") => 1e9,
+            (MetricKind::Size, "TB") => 1e12,
+            _ => return Err(format!("invalid unit in threshold: {}", s)),
+        };
+        Ok(number * scale)
+    }
+}
+
+/// Display settings the stat formatters read, resolved from the config once
+/// per render
+pub struct RenderContext {
+    /// `[display.formats]` value templates
+    pub formats: HashMap<StatId, String>,
+    /// `[display.size]` units and precision
+    pub size: SizeConfig,
+    /// `[display] locale` labels, durations and number formatting
+    pub locale: Locale,
+    /// `[display.timestamps]` format by stat
+    pub timestamps: HashMap<StatId, TimestampFormat>,
+    pub installed_breakdown: bool,
+    /// Whether Flatpak updates count towards `upgradable`
+    pub flatpak_in_upgradable: bool,
+}
+
+impl RenderContext {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            formats: config.display.parsed_formats(),
+            size: config.display.size.clone(),
+            locale: Locale::load(&config.display.locale),
+            timestamps: config.display.parsed_timestamps(),
+            installed_breakdown: config.display.installed_breakdown,
+            flatpak_in_upgradable: config.flatpak.include_in_upgradable,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PaletteVariant {
     Both,
@@ -48,10 +146,10 @@ pub enum StatIdOrTitle {
     LegacyTitle,
     ColorPalette(PaletteVariant),
     Newline,
+    /// A `[display.custom.<name>]` command stat
+    Custom(String),
 }
 
-const BYTES_PER_GIB: f64 = 1073741824.0;
-
 impl StatId {
     /// Parse a stat string, handling both regular stats and title.{name} references
     pub fn parse(s: &str) -> Result<StatIdOrTitle, String> {
@@ -62,6 +160,13 @@ impl StatId {
             return Ok(StatIdOrTitle::NamedTitle(name.to_string()));
         }
 
+        if let Some(name) = s.strip_prefix("custom.") {
+            if name.is_empty() {
+                return Err("custom stat name cannot be empty".to_string());
+            }
+            return Ok(StatIdOrTitle::Custom(name.to_string()));
+        }
+
         if s == "title" {
             return Ok(StatIdOrTitle::LegacyTitle);
         }
@@ -72,12 +177,21 @@ impl StatId {
 
         match s {
             "installed" => Ok(StatIdOrTitle::Stat(StatId::Installed)),
+            "explicit_installed" => Ok(StatIdOrTitle::Stat(StatId::ExplicitInstalled)),
+            "dependency_installed" => Ok(StatIdOrTitle::Stat(StatId::DependencyInstalled)),
             "upgradable" => Ok(StatIdOrTitle::Stat(StatId::Upgradable)),
+            "aur_upgradable" => Ok(StatIdOrTitle::Stat(StatId::AurUpgradable)),
+            "flatpak_upgradable" => Ok(StatIdOrTitle::Stat(StatId::FlatpakUpgradable)),
             "last_update" => Ok(StatIdOrTitle::Stat(StatId::LastUpdate)),
             "download_size" => Ok(StatIdOrTitle::Stat(StatId::DownloadSize)),
             "installed_size" => Ok(StatIdOrTitle::Stat(StatId::InstalledSize)),
             "net_upgrade_size" => Ok(StatIdOrTitle::Stat(StatId::NetUpgradeSize)),
             "orphaned_packages" => Ok(StatIdOrTitle::Stat(StatId::OrphanedPackages)),
+            "vulnerable_packages" => Ok(StatIdOrTitle::Stat(StatId::VulnerablePackages)),
+            "largest_packages" => Ok(StatIdOrTitle::Stat(StatId::LargestPackages)),
+            "recent_packages" => Ok(StatIdOrTitle::Stat(StatId::RecentPackages)),
+            "keyring" => Ok(StatIdOrTitle::Stat(StatId::Keyring)),
+            "db_lock" => Ok(StatIdOrTitle::Stat(StatId::DbLock)),
             "cache_size" => Ok(StatIdOrTitle::Stat(StatId::CacheSize)),
             "mirror_url" => Ok(StatIdOrTitle::Stat(StatId::MirrorUrl)),
             "mirror_health" => Ok(StatIdOrTitle::Stat(StatId::MirrorHealth)),
@@ -93,12 +207,21 @@ impl StatId {
         match self {
             StatId::Title => "title",
             StatId::Installed => "installed",
+            StatId::ExplicitInstalled => "explicit_installed",
+            StatId::DependencyInstalled => "dependency_installed",
             StatId::Upgradable => "upgradable",
+            StatId::AurUpgradable => "aur_upgradable",
+            StatId::FlatpakUpgradable => "flatpak_upgradable",
             StatId::LastUpdate => "last_update",
             StatId::DownloadSize => "download_size",
             StatId::InstalledSize => "installed_size",
             StatId::NetUpgradeSize => "net_upgrade_size",
             StatId::OrphanedPackages => "orphaned_packages",
+            StatId::VulnerablePackages => "vulnerable_packages",
+            StatId::LargestPackages => "largest_packages",
+            StatId::RecentPackages => "recent_packages",
+            StatId::Keyring => "keyring",
+            StatId::DbLock => "db_lock",
             StatId::CacheSize => "cache_size",
             StatId::MirrorUrl => "mirror_url",
             StatId::MirrorHealth => "mirror_health",
@@ -110,12 +233,21 @@ impl StatId {
         match self {
             StatId::Title => "",
             StatId::Installed => "Installed",
+            StatId::ExplicitInstalled => "Explicitly Installed",
+            StatId::DependencyInstalled => "Installed as Dependency",
             StatId::Upgradable => "Upgradable",
+            StatId::AurUpgradable => "AUR Upgradable",
+            StatId::FlatpakUpgradable => "Flatpak Upgradable",
             StatId::LastUpdate => "Last System Update",
             StatId::DownloadSize => "Download Size",
             StatId::InstalledSize => "Installed Size",
             StatId::NetUpgradeSize => "Net Upgrade Size",
             StatId::OrphanedPackages => "Orphaned Packages",
+            StatId::VulnerablePackages => "Vulnerable Packages",
+            StatId::LargestPackages => "Largest Packages",
+            StatId::RecentPackages => "Recent Packages",
+            StatId::Keyring => "Keyring",
+            StatId::DbLock => "Database Lock",
             StatId::CacheSize => "Package Cache",
             StatId::MirrorUrl => "Mirror URL",
             StatId::MirrorHealth => "Mirror Health",
@@ -123,24 +255,374 @@ impl StatId {
         }
     }
 
-    pub fn format_value(&self, stats: &PacmanStats) -> Option<String> {
+    /// Names usable as `{name}` in this stat's `[display.formats]` template
+    pub fn placeholders(&self) -> &'static [&'static str] {
+        match self {
+            StatId::Title => &[],
+            StatId::Installed => &["count", "explicit"],
+            StatId::ExplicitInstalled | StatId::DependencyInstalled => &["count"],
+            StatId::Upgradable => &["count", "repo", "aur", "flatpak"],
+            StatId::AurUpgradable => &["count"],
+            StatId::FlatpakUpgradable => &["count", "system", "user"],
+            StatId::LastUpdate => &["ago", "seconds", "date"],
+            StatId::DownloadSize
+            | StatId::InstalledSize
+            | StatId::NetUpgradeSize
+            | StatId::CacheSize => &["size", "bytes"],
+            StatId::OrphanedPackages => &["count", "size", "bytes"],
+            StatId::VulnerablePackages => &["count", "fixed_by_upgrade", "fix_pending", "no_fix"],
+            StatId::LargestPackages => &["count", "size", "bytes"],
+            StatId::RecentPackages => &[
+                "count",
+                "upgraded",
+                "installed",
+                "downgraded",
+                "reinstalled",
+            ],
+            StatId::Keyring => &["installed", "available"],
+            StatId::DbLock => &["state", "age", "holders"],
+            StatId::MirrorUrl => &["url"],
+            StatId::MirrorHealth => &["hours"],
+            StatId::Disk => &["used", "total", "percent"],
+        }
+    }
+
+    /// What `[display.thresholds]` compare for this stat, None if it has
+    /// no numeric value
+    pub fn metric_kind(&self) -> Option<MetricKind> {
+        match self {
+            StatId::Installed
+            | StatId::ExplicitInstalled
+            | StatId::DependencyInstalled
+            | StatId::Upgradable
+            | StatId::AurUpgradable
+            | StatId::FlatpakUpgradable
+            | StatId::OrphanedPackages
+            | StatId::VulnerablePackages
+            | StatId::RecentPackages => Some(MetricKind::Count),
+            StatId::LastUpdate | StatId::DbLock | StatId::MirrorHealth => {
+                Some(MetricKind::Duration)
+            }
+            StatId::DownloadSize
+            | StatId::InstalledSize
+            | StatId::NetUpgradeSize
+            | StatId::CacheSize
+            | StatId::LargestPackages => Some(MetricKind::Size),
+            StatId::Disk => Some(MetricKind::Percent),
+            StatId::Title | StatId::Keyring | StatId::MirrorUrl => None,
+        }
+    }
+
+    /// The value thresholds are compared against, in the unit of `metric_kind`
+    pub fn metric(&self, stats: &PacmanStats, ctx: &RenderContext) -> Option<f64> {
+        if stats.timed_out.contains(self) {
+            return None;
+        }
+        let value = match self {
+            StatId::Installed => stats.total_installed? as f64,
+            StatId::ExplicitInstalled => stats.explicit_installed? as f64,
+            StatId::DependencyInstalled => stats.dependency_installed? as f64,
+            StatId::Upgradable => upgradable_counts(stats, ctx)?.total() as f64,
+            StatId::AurUpgradable => match stats.aur_upgradable? {
+                AurStatus::Upgradable(n) => n as f64,
+                AurStatus::Unreachable | AurStatus::InvalidSignature => return None,
+            },
+            StatId::FlatpakUpgradable => stats.flatpak_upgradable.as_ref()?.count() as f64,
+            StatId::OrphanedPackages => stats.orphaned_packages? as f64,
+            StatId::VulnerablePackages => stats.vulnerable_packages.as_ref()?.count() as f64,
+            StatId::RecentPackages => stats.recent_packages.as_ref()?.len() as f64,
+            StatId::LastUpdate => stats.days_since_last_update? as f64,
+            StatId::DbLock => match stats.db_lock.as_ref()? {
+                DbLockStatus::Unlocked => return None,
+                DbLockStatus::Held { age_secs, .. } | DbLockStatus::Stale { age_secs } => {
+                    *age_secs as f64
+                }
+            },
+            StatId::MirrorHealth => stats.mirror_sync_age_hours? * 3600.0,
+            StatId::DownloadSize => stats.download_size_bytes? as f64,
+            StatId::InstalledSize => stats.installed_size_bytes? as f64,
+            StatId::NetUpgradeSize => stats.net_upgrade_size_bytes? as f64,
+            StatId::CacheSize => stats.cache_size_bytes? as f64,
+            StatId::LargestPackages => stats
+                .largest_packages
+                .as_ref()?
+                .iter()
+                .map(|p| p.size_bytes as f64)
+                .sum(),
+            StatId::Disk => {
+                let (used, total) = (stats.disk_used_bytes?, stats.disk_total_bytes?);
+                if total == 0 {
+                    return None;
+                }
+                used as f64 / total as f64 * 100.0
+            }
+            StatId::Title | StatId::Keyring | StatId::MirrorUrl => return None,
+        };
+        Some(value)
+    }
+
+    /// Placeholder values for this stat's template. None when there is no
+    /// value or it is an error, which keep their built-in text.
+    fn template_values(
+        &self,
+        stats: &PacmanStats,
+        ctx: &RenderContext,
+    ) -> Option<Vec<(&'static str, String)>> {
+        let size = |bytes: i64| {
+            vec![
+                ("size", util::format_size(bytes, &ctx.size, &ctx.locale)),
+                ("bytes", bytes.to_string()),
+            ]
+        };
+        let values = match self {
+            StatId::Title => return None,
+            StatId::Installed => vec![
+                ("count", stats.total_installed?.to_string()),
+                (
+                    "explicit",
+                    stats
+                        .explicit_installed
+                        .map(|c| c.to_string())
+                        .unwrap_or_default(),
+                ),
+            ],
+            StatId::ExplicitInstalled => vec![("count", stats.explicit_installed?.to_string())],
+            StatId::DependencyInstalled => {
+                vec![("count", stats.dependency_installed?.to_string())]
+            }
+            StatId::Upgradable => {
+                let counts = upgradable_counts(stats, ctx)?;
+                // A source without a count shows as unavailable, not 0
+                let part = |n: Option<u32>| n.map_or("-".to_string(), |n| n.to_string());
+                vec![
+                    ("count", counts.total().to_string()),
+                    ("repo", counts.repo.to_string()),
+                    ("aur", part(counts.aur)),
+                    ("flatpak", part(counts.flatpak)),
+                ]
+            }
+            StatId::AurUpgradable => match stats.aur_upgradable? {
+                AurStatus::Upgradable(n) => vec![("count", n.to_string())],
+                AurStatus::Unreachable | AurStatus::InvalidSignature => return None,
+            },
+            StatId::FlatpakUpgradable => {
+                let fp = stats.flatpak_upgradable.as_ref()?;
+                vec![
+                    ("count", fp.count().to_string()),
+                    ("system", fp.system.len().to_string()),
+                    ("user", fp.user.len().to_string()),
+                ]
+            }
+            StatId::LastUpdate => {
+                let seconds = stats.days_since_last_update?;
+                let date = stats.last_update_time?.format(util::ABSOLUTE_TIMESTAMP);
+                vec![
+                    ("ago", ctx.locale.duration(seconds)),
+                    ("seconds", seconds.to_string()),
+                    ("date", date.to_string()),
+                ]
+            }
+            StatId::DownloadSize
+            | StatId::InstalledSize
+            | StatId::NetUpgradeSize
+            | StatId::CacheSize => {
+                let bytes = match self {
+                    StatId::DownloadSize => stats.download_size_bytes,
+                    StatId::InstalledSize => stats.installed_size_bytes,
+                    StatId::NetUpgradeSize => stats.net_upgrade_size_bytes,
+                    _ => stats.cache_size_bytes.map(|b| b as i64),
+                }?;
+                size(bytes)
+            }
+            StatId::OrphanedPackages => {
+                let mut values = vec![("count", stats.orphaned_packages?.to_string())];
+                values.extend(size(stats.orphaned_size_bytes.unwrap_or(0)));
+                values
+            }
+            StatId::VulnerablePackages => {
+                let vuln = stats.vulnerable_packages.as_ref()?;
+                vec![
+                    ("count", vuln.count().to_string()),
+                    ("fixed_by_upgrade", vuln.fixed_by_upgrade().to_string()),
+                    ("fix_pending", vuln.fix_pending().to_string()),
+                    ("no_fix", vuln.no_fix().to_string()),
+                ]
+            }
+            StatId::LargestPackages => {
+                let pkgs = stats.largest_packages.as_ref()?;
+                let total: i64 = pkgs.iter().map(|p| p.size_bytes).sum();
+                let mut values = vec![("count", pkgs.len().to_string())];
+                values.extend(size(total));
+                values
+            }
+            StatId::RecentPackages => {
+                let pkgs = stats.recent_packages.as_ref()?;
+                let count = |change: PackageChange| {
+                    pkgs.iter()
+                        .filter(|p| p.change == change)
+                        .count()
+                        .to_string()
+                };
+                vec![
+                    ("count", pkgs.len().to_string()),
+                    ("upgraded", count(PackageChange::Upgraded)),
+                    ("installed", count(PackageChange::Installed)),
+                    ("downgraded", count(PackageChange::Downgraded)),
+                    ("reinstalled", count(PackageChange::Reinstalled)),
+                ]
+            }
+            StatId::Keyring => {
+                let keyring = stats.keyring.as_ref()?;
+                vec![
+                    ("installed", keyring.installed.clone()),
+                    ("available", keyring.available.clone().unwrap_or_default()),
+                ]
+            }
+            StatId::DbLock => match stats.db_lock.as_ref()? {
+                DbLockStatus::Unlocked => vec![
+                    ("state", "unlocked".to_string()),
+                    ("age", String::new()),
+                    ("holders", String::new()),
+                ],
+                DbLockStatus::Held { age_secs, holders } => {
+                    let names: Vec<String> = holders
+                        .iter()
+                        .map(|h| format!("{} ({})", h.name, h.pid))
+                        .collect();
+                    vec![
+                        ("state", "held".to_string()),
+                        ("age", ctx.locale.duration(*age_secs)),
+                        ("holders", names.join(", ")),
+                    ]
+                }
+                DbLockStatus::Stale { age_secs } => vec![
+                    ("state", "stale".to_string()),
+                    ("age", ctx.locale.duration(*age_secs)),
+                    ("holders", String::new()),
+                ],
+            },
+            StatId::MirrorUrl => vec![("url", stats.mirror_url.clone()?)],
+            StatId::MirrorHealth => {
+                stats.mirror_url.as_ref()?;
+                vec![("hours", ctx.locale.decimal(stats.mirror_sync_age_hours?, 1))]
+            }
+            StatId::Disk => {
+                let (used, total) = (stats.disk_used_bytes?, stats.disk_total_bytes?);
+                let pct = if total > 0 {
+                    (used as f64 / total as f64) * 100.0
+                } else {
+                    0.0
+                };
+                vec![
+                    (
+                        "used",
+                        util::format_size(used as i64, &ctx.size, &ctx.locale),
+                    ),
+                    (
+                        "total",
+                        util::format_size(total as i64, &ctx.size, &ctx.locale),
+                    ),
+                    ("percent", format!("{:.0}%", pct)),
+                ]
+            }
+        };
+        Some(values)
+    }
+
+    /// The stat's `[display.formats]` template filled in, with `style`
+    /// applied to one placeholder's value. None without a template or value.
+    pub fn format_template_with(
+        &self,
+        stats: &PacmanStats,
+        ctx: &RenderContext,
+        placeholder: &str,
+        style: impl Fn(&str) -> String,
+    ) -> Option<String> {
+        let template = ctx.formats.get(self)?;
+        let values: Vec<(&str, String)> = self
+            .template_values(stats, ctx)?
+            .into_iter()
+            .map(|(name, value)| {
+                let value = if name == placeholder {
+                    style(&value)
+                } else {
+                    value
+                };
+                (name, value)
+            })
+            .collect();
+        Some(render_template(template, &values))
+    }
+
+    pub fn format_value(&self, stats: &PacmanStats, ctx: &RenderContext) -> Option<String> {
+        if stats.timed_out.contains(self) {
+            return Some(ctx.locale.message("timed_out", &[]));
+        }
+
+        if let Some(template) = ctx.formats.get(self)
+            && let Some(values) = self.template_values(stats, ctx)
+        {
+            return Some(render_template(template, &values));
+        }
+
+        let num = |n: u32| ctx.locale.integer(n as i64);
         match self {
             StatId::Title => None,
-            StatId::Installed => Some(stats.total_installed.to_string()),
-            StatId::Upgradable => Some(stats.total_upgradable.to_string()),
-            StatId::LastUpdate => stats.days_since_last_update.map(util::normalize_duration),
-            StatId::DownloadSize => stats.download_size_mb.map(|s| format!("{:.2} MiB", s)),
+            StatId::Installed => {
+                let total = stats.total_installed?;
+                match stats.explicit_installed {
+                    Some(explicit) if ctx.installed_breakdown => Some(ctx.locale.message(
+                        "installed_explicit",
+                        &[("total", num(total)), ("explicit", num(explicit))],
+                    )),
+                    _ => Some(num(total)),
+                }
+            }
+            StatId::ExplicitInstalled => stats.explicit_installed.map(num),
+            StatId::DependencyInstalled => stats.dependency_installed.map(num),
+            StatId::Upgradable => format_upgradable(stats, ctx),
+            StatId::AurUpgradable => stats.aur_upgradable.map(|status| match status {
+                AurStatus::Upgradable(count) => num(count),
+                _ => error_value(ctx, &aur_error(ctx, status).unwrap_or_default()),
+            }),
+            StatId::FlatpakUpgradable => match stats.flatpak_upgradable.as_ref() {
+                Some(fp) if fp.count() > 0 => Some(ctx.locale.message(
+                    "flatpak_breakdown",
+                    &[
+                        ("count", num(fp.count())),
+                        ("system", num(fp.system.len() as u32)),
+                        ("user", num(fp.user.len() as u32)),
+                    ],
+                )),
+                Some(_) => Some("0".to_string()),
+                None => stats
+                    .collected
+                    .contains(&StatId::FlatpakUpgradable)
+                    .then(|| error_value(ctx, &ctx.locale.message("flatpak_check_failed", &[]))),
+            },
+            StatId::LastUpdate => stats.last_update_time.map(|t| {
+                util::format_timestamp(t, &timestamp_format(ctx, StatId::LastUpdate), &ctx.locale)
+            }),
+            StatId::DownloadSize => stats
+                .download_size_bytes
+                .map(|b| util::format_size(b, &ctx.size, &ctx.locale)),
             StatId::InstalledSize => stats
-                .total_installed_size_mb
-                .map(|s| format!("{:.2} MiB", s)),
-            StatId::NetUpgradeSize => stats.net_upgrade_size_mb.map(|s| format!("{:.2} MiB", s)),
+                .installed_size_bytes
+                .map(|b| util::format_size(b, &ctx.size, &ctx.locale)),
+            StatId::NetUpgradeSize => stats
+                .net_upgrade_size_bytes
+                .map(|b| util::format_size(b, &ctx.size, &ctx.locale)),
             StatId::OrphanedPackages => {
                 if let Some(count) = stats.orphaned_packages {
                     if count > 0 {
-                        if let Some(size) = stats.orphaned_size_mb {
-                            Some(format!("{} ({:.2} MiB)", count, size))
+                        if let Some(size) = stats.orphaned_size_bytes {
+                            Some(format!(
+                                "{} ({})",
+                                num(count),
+                                util::format_size(size, &ctx.size, &ctx.locale)
+                            ))
                         } else {
-                            Some(count.to_string())
+                            Some(num(count))
                         }
                     } else {
                         Some("0".to_string())
@@ -149,25 +631,111 @@ impl StatId {
                     None
                 }
             }
-            StatId::CacheSize => stats.cache_size_mb.map(|s| format!("{:.2} MiB", s)),
+            StatId::VulnerablePackages => stats.vulnerable_packages.as_ref().map(|vuln| {
+                if vuln.count() == 0 {
+                    return "0".to_string();
+                }
+                let parts: Vec<String> = [
+                    (vuln.fixed_by_upgrade(), "vuln_fixed_by_upgrade"),
+                    (vuln.fix_pending(), "vuln_fix_pending"),
+                    (vuln.no_fix(), "vuln_no_fix"),
+                ]
+                .iter()
+                .filter(|(n, _)| *n > 0)
+                .map(|(n, key)| ctx.locale.message(key, &[("count", num(*n))]))
+                .collect();
+                format!("{} ({})", num(vuln.count()), parts.join(", "))
+            }),
+            StatId::LargestPackages => stats.largest_packages.as_ref().map(|pkgs| {
+                let total: i64 = pkgs.iter().map(|p| p.size_bytes).sum();
+                ctx.locale.message(
+                    "largest_in_top",
+                    &[
+                        ("size", util::format_size(total, &ctx.size, &ctx.locale)),
+                        ("count", num(pkgs.len() as u32)),
+                    ],
+                )
+            }),
+            StatId::RecentPackages => stats.recent_packages.as_ref().map(|pkgs| {
+                let parts: Vec<String> = [
+                    PackageChange::Upgraded,
+                    PackageChange::Installed,
+                    PackageChange::Downgraded,
+                    PackageChange::Reinstalled,
+                ]
+                .iter()
+                .map(|change| (pkgs.iter().filter(|p| p.change == *change).count(), change))
+                .filter(|(n, _)| *n > 0)
+                .map(|(n, change)| format!("{} {}", num(n as u32), change_text(ctx, change)))
+                .collect();
+                if parts.is_empty() {
+                    "0".to_string()
+                } else {
+                    format!("{} ({})", num(pkgs.len() as u32), parts.join(", "))
+                }
+            }),
+            StatId::Keyring => stats.keyring.as_ref().map(|k| match &k.available {
+                Some(new) => ctx.locale.message(
+                    "keyring_outdated",
+                    &[
+                        ("installed", k.installed.clone()),
+                        ("available", new.clone()),
+                    ],
+                ),
+                None => ctx
+                    .locale
+                    .message("keyring_up_to_date", &[("installed", k.installed.clone())]),
+            }),
+            StatId::DbLock => stats.db_lock.as_ref().map(|lock| match lock {
+                DbLockStatus::Unlocked => ctx.locale.message("db_lock_unlocked", &[]),
+                DbLockStatus::Held { age_secs, holders } => {
+                    let names: Vec<String> = holders
+                        .iter()
+                        .map(|h| format!("{} ({})", h.name, h.pid))
+                        .collect();
+                    let (key, time) = lock_age(ctx, *age_secs);
+                    ctx.locale.message(
+                        &format!("db_lock_held{}", key),
+                        &[("holders", names.join(", ")), ("age", time)],
+                    )
+                }
+                DbLockStatus::Stale { age_secs } => {
+                    let (key, time) = lock_age(ctx, *age_secs);
+                    ctx.locale
+                        .message(&format!("db_lock_stale{}", key), &[("age", time)])
+                }
+            }),
+            StatId::CacheSize => stats
+                .cache_size_bytes
+                .map(|b| util::format_size(b as i64, &ctx.size, &ctx.locale)),
             StatId::MirrorUrl => stats.mirror_url.clone(),
             StatId::MirrorHealth => match (&stats.mirror_url, stats.mirror_sync_age_hours) {
-                (Some(_), Some(age)) => Some(format!("OK (last sync {:.1} hours)", age)),
-                (Some(_), None) => Some("Err - could not check sync status".to_string()),
-                (None, _) => Some("Err - no mirror found".to_string()),
+                (Some(_), Some(age)) => Some(format!(
+                    "{} ({})",
+                    ctx.locale.message("mirror_ok", &[]),
+                    mirror_last_sync(ctx, age)
+                )),
+                (Some(_), None) => Some(error_value(
+                    ctx,
+                    &ctx.locale.message("mirror_unchecked", &[]),
+                )),
+                (None, _) => Some(error_value(
+                    ctx,
+                    &ctx.locale.message("mirror_not_found", &[]),
+                )),
             },
             StatId::Disk => {
                 if let (Some(used), Some(total)) = (stats.disk_used_bytes, stats.disk_total_bytes) {
-                    let used_gib = used as f64 / BYTES_PER_GIB;
-                    let total_gib = total as f64 / BYTES_PER_GIB;
                     let pct = if total > 0 {
                         (used as f64 / total as f64) * 100.0
                     } else {
                         0.0
                     };
                     Some(format!(
-                        "{:.2} GiB / {:.2} GiB ({:.0}%)",
-                        used_gib, total_gib, pct
+                        "{} / {} ({:.0}%)",
+                        util::format_size(used as i64, &ctx.size, &ctx.locale),
+                        util::format_size(total as i64, &ctx.size, &ctx.locale),
+                        pct
                     ))
                 } else {
                     None
@@ -175,6 +743,461 @@ impl StatId {
             }
         }
     }
+
+    /// Typed value for `--json`: counts as numbers, sizes in bytes and
+    /// durations in seconds. The error is why the stat has no value.
+    pub fn json_value(
+        &self,
+        stats: &PacmanStats,
+        ctx: &RenderContext,
+    ) -> Result<serde_json::Value, String> {
+        use serde_json::json;
+
+        if stats.timed_out.contains(self) {
+            return Err("timed out".to_string());
+        }
+        // A collector that ran and left no value failed
+        let collected = stats.collected.contains(self);
+        let missing = || if collected { "failed" } else { "not collected" }.to_string();
+
+        let value = match self {
+            StatId::Title => return Err("not a stat".to_string()),
+            StatId::Installed => json!(stats.total_installed.ok_or_else(missing)?),
+            StatId::ExplicitInstalled => json!(stats.explicit_installed.ok_or_else(missing)?),
+            StatId::DependencyInstalled => {
+                json!(stats.dependency_installed.ok_or_else(missing)?)
+            }
+            StatId::Upgradable => {
+                if !stats.invalid_repos.is_empty() {
+                    return Err(format!(
+                        "invalid signature: {}",
+                        stats.invalid_repos.join(", ")
+                    ));
+                }
+                let counts = upgradable_counts(stats, ctx).ok_or_else(missing)?;
+                json!({
+                    "total": counts.total(),
+                    "repo": counts.repo,
+                    "aur": counts.aur,
+                    "flatpak": counts.flatpak,
+                })
+            }
+            StatId::AurUpgradable => match stats.aur_upgradable.ok_or_else(missing)? {
+                AurStatus::Upgradable(n) => json!(n),
+                status => return Err(status.error().unwrap_or_default().to_string()),
+            },
+            StatId::FlatpakUpgradable => {
+                let fp = stats.flatpak_upgradable.as_ref().ok_or_else(missing)?;
+                json!({
+                    "count": fp.count(),
+                    "system": fp.system,
+                    "user": fp.user,
+                })
+            }
+            StatId::LastUpdate => {
+                let seconds = stats.days_since_last_update.ok_or_else(missing)?;
+                json!({
+                    "seconds": seconds,
+                    "time": stats.last_update_time.map(|t| t.to_rfc3339()),
+                })
+            }
+            StatId::DownloadSize => {
+                json!({ "bytes": stats.download_size_bytes.ok_or_else(missing)? })
+            }
+            StatId::InstalledSize => {
+                json!({ "bytes": stats.installed_size_bytes.ok_or_else(missing)? })
+            }
+            StatId::NetUpgradeSize => {
+                json!({ "bytes": stats.net_upgrade_size_bytes.ok_or_else(missing)? })
+            }
+            StatId::CacheSize => json!({ "bytes": stats.cache_size_bytes.ok_or_else(missing)? }),
+            StatId::OrphanedPackages => json!({
+                "count": stats.orphaned_packages.ok_or_else(missing)?,
+                "bytes": stats.orphaned_size_bytes,
+            }),
+            StatId::VulnerablePackages => {
+                let vuln = stats.vulnerable_packages.as_ref().ok_or_else(missing)?;
+                let severity: serde_json::Map<String, serde_json::Value> = vuln
+                    .severity_counts()
+                    .into_iter()
+                    .map(|(sev, n)| (sev.to_lowercase(), n.into()))
+                    .collect();
+                let packages: Vec<serde_json::Value> = vuln
+                    .packages
+                    .iter()
+                    .map(|p| {
+                        json!({
+                            "name": p.name,
+                            "installed_version": p.installed_version,
+                            "severity": p.severity.to_lowercase(),
+                            "fixed_version": p.fixed_version,
+                            "fixed_by_upgrade": p.fixed_by_upgrade,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "count": vuln.count(),
+                    "fixed_by_upgrade": vuln.fixed_by_upgrade(),
+                    "fix_pending": vuln.fix_pending(),
+                    "no_fix": vuln.no_fix(),
+                    "severity": severity,
+                    "packages": packages,
+                })
+            }
+            StatId::LargestPackages => {
+                let pkgs = stats.largest_packages.as_ref().ok_or_else(missing)?;
+                pkgs.iter()
+                    .map(|p| {
+                        json!({
+                            "name": p.name,
+                            "size_bytes": p.size_bytes,
+                            "exclusive_deps": p.exclusive_deps,
+                        })
+                    })
+                    .collect()
+            }
+            StatId::RecentPackages => {
+                let pkgs = stats.recent_packages.as_ref().ok_or_else(missing)?;
+                pkgs.iter()
+                    .map(|p| {
+                        json!({
+                            "name": p.name,
+                            "version": p.version,
+                            "change": p.change.as_str(),
+                            "seconds_ago": p.seconds_ago,
+                        })
+                    })
+                    .collect()
+            }
+            StatId::Keyring => {
+                let k = stats.keyring.as_ref().ok_or_else(missing)?;
+                json!({
+                    "installed": k.installed,
+                    "available": k.available,
+                    "up_to_date": k.available.is_none(),
+                })
+            }
+            StatId::DbLock => match stats.db_lock.as_ref().ok_or_else(missing)? {
+                DbLockStatus::Unlocked => json!({ "state": "unlocked" }),
+                DbLockStatus::Held { age_secs, holders } => json!({
+                    "state": "held",
+                    "age_seconds": age_secs,
+                    "holders": holders
+                        .iter()
+                        .map(|h| json!({ "pid": h.pid, "name": h.name }))
+                        .collect::<Vec<_>>(),
+                }),
+                DbLockStatus::Stale { age_secs } => json!({
+                    "state": "stale",
+                    "age_seconds": age_secs,
+                }),
+            },
+            StatId::MirrorUrl | StatId::MirrorHealth
+                if stats.mirror_url.is_none() && !collected =>
+            {
+                return Err(missing());
+            }
+            StatId::MirrorUrl => json!(stats.mirror_url.as_ref().ok_or("no mirror found")?),
+            StatId::MirrorHealth => {
+                if stats.mirror_url.is_none() {
+                    return Err("no mirror found".to_string());
+                }
+                let hours = stats
+                    .mirror_sync_age_hours
+                    .ok_or("could not check sync status")?;
+                json!({ "sync_age_seconds": (hours * 3600.0).round() as i64 })
+            }
+            StatId::Disk => {
+                let used = stats.disk_used_bytes.ok_or_else(missing)?;
+                let total = stats.disk_total_bytes.ok_or_else(missing)?;
+                json!({
+                    "used_bytes": used,
+                    "total_bytes": total,
+                    "percent": self.metric(stats, ctx),
+                })
+            }
+        };
+        Ok(value)
+    }
+
+    /// Parts of this stat's JSON value that are null, keyed like
+    /// `upgradable.aur`, with the reason for each
+    pub fn unavailable_parts(
+        &self,
+        stats: &PacmanStats,
+        ctx: &RenderContext,
+    ) -> Vec<(String, String)> {
+        let Some(counts) = (*self == StatId::Upgradable)
+            .then(|| upgradable_counts(stats, ctx))
+            .flatten()
+        else {
+            return Vec::new();
+        };
+        [
+            ("aur", counts.aur, StatId::AurUpgradable),
+            ("flatpak", counts.flatpak, StatId::FlatpakUpgradable),
+        ]
+        .into_iter()
+        .filter(|(_, count, _)| count.is_none())
+        .map(|(part, _, source)| {
+            (
+                format!("{}.{}", self.config_key(), part),
+                source_unavailable(stats, source),
+            )
+        })
+        .collect()
+    }
+
+    /// Extra rows shown under the stat line, for stats that list items
+    pub fn format_rows(&self, stats: &PacmanStats, ctx: &RenderContext) -> Option<Vec<String>> {
+        if stats.timed_out.contains(self) {
+            return None;
+        }
+        match self {
+            StatId::LargestPackages => {
+                let pkgs = stats.largest_packages.as_ref()?;
+                let name_width = pkgs.iter().map(|p| p.name.chars().count()).max()?;
+                Some(
+                    pkgs.iter()
+                        .map(|p| {
+                            format!(
+                                "{:<width$}  {:>12}",
+                                p.name,
+                                util::format_size(p.size_bytes, &ctx.size, &ctx.locale),
+                                width = name_width
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            StatId::RecentPackages => {
+                let pkgs = stats.recent_packages.as_ref()?;
+                let name_width = pkgs.iter().map(|p| p.name.chars().count()).max()?;
+                let version_width = pkgs.iter().map(|p| p.version.chars().count()).max()?;
+                Some(
+                    pkgs.iter()
+                        .map(|p| {
+                            format!(
+                                "{:<name_width$}  {:<version_width$}  {:<10}  {}",
+                                p.name,
+                                p.version,
+                                change_text(ctx, &p.change),
+                                format_ago(ctx, StatId::RecentPackages, p.seconds_ago),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `{name}` placeholders used in a format template
+pub fn template_placeholders(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        names.push(&rest[start + 1..start + len]);
+        rest = &rest[start + len + 1..];
+    }
+    names
+}
+
+/// Fill a format template's `{name}` placeholders. Unknown ones are kept as is.
+fn render_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut out = template.to_string();
+    for (name, value) in values {
+        out = out.replace(&format!("{{{}}}", name), value);
+    }
+    out
+}
+
+/// Value line of a custom stat, None if it was never run
+pub fn format_custom_value(name: &str, stats: &PacmanStats, ctx: &RenderContext) -> Option<String> {
+    stats.custom.get(name).map(|output| match output {
+        CustomOutput::Value(value) => value.clone(),
+        CustomOutput::Failed(reason) => error_value(ctx, reason),
+        CustomOutput::TimedOut => ctx.locale.message("timed_out", &[]),
+    })
+}
+
+/// Upgradable counts by source. AUR and Flatpak are None when their check
+/// failed or didn't run; Flatpak is 0 when it isn't counted towards upgradable.
+struct UpgradableCounts {
+    repo: u32,
+    aur: Option<u32>,
+    flatpak: Option<u32>,
+}
+
+impl UpgradableCounts {
+    /// The sources that have a count, added up
+    fn total(&self) -> u32 {
+        self.repo + self.aur.unwrap_or(0) + self.flatpak.unwrap_or(0)
+    }
+}
+
+/// None if the upgrade check failed or a sync db is invalid
+fn upgradable_counts(stats: &PacmanStats, ctx: &RenderContext) -> Option<UpgradableCounts> {
+    if !stats.invalid_repos.is_empty() {
+        return None;
+    }
+    let repo = stats.total_upgradable?;
+    let aur = match stats.aur_upgradable {
+        Some(AurStatus::Upgradable(n)) => Some(n),
+        _ => None,
+    };
+    let flatpak = match &stats.flatpak_upgradable {
+        _ if !ctx.flatpak_in_upgradable => Some(0),
+        Some(fp) => Some(fp.count()),
+        None => None,
+    };
+    Some(UpgradableCounts { repo, aur, flatpak })
+}
+
+/// Why a source stat has no count, for JSON `unavailable`
+fn source_unavailable(stats: &PacmanStats, id: StatId) -> String {
+    if stats.timed_out.contains(&id) {
+        return "timed out".to_string();
+    }
+    if id == StatId::AurUpgradable
+        && let Some(error) = stats.aur_upgradable.and_then(|status| status.error())
+    {
+        return error.to_string();
+    }
+    if stats.collected.contains(&id) {
+        "failed".to_string()
+    } else {
+        "not collected".to_string()
+    }
+}
+
+fn timestamp_format(ctx: &RenderContext, id: StatId) -> TimestampFormat {
+    ctx.timestamps.get(&id).cloned().unwrap_or_default()
+}
+
+/// A time `seconds` back in the stat's `[display.timestamps]` format, as
+/// "3 hours ago" for the relative formats
+fn format_ago(ctx: &RenderContext, id: StatId, seconds: i64) -> String {
+    let format = timestamp_format(ctx, id);
+    let time = Local::now() - chrono::Duration::seconds(seconds);
+    let text = util::format_timestamp(time, &format, &ctx.locale);
+    if format.is_relative() {
+        ctx.locale.message("ago", &[("time", text)])
+    } else {
+        text
+    }
+}
+
+/// The db_lock message suffix and age text: "for 3 minutes" reads as
+/// "since 14:32" for the absolute formats
+fn lock_age(ctx: &RenderContext, age_secs: i64) -> (&'static str, String) {
+    let format = timestamp_format(ctx, StatId::DbLock);
+    let time = Local::now() - chrono::Duration::seconds(age_secs);
+    let suffix = if format.is_relative() { "" } else { "_since" };
+    (suffix, util::format_timestamp(time, &format, &ctx.locale))
+}
+
+/// "last sync 2.5 hours" for mirror_health
+pub fn mirror_last_sync(ctx: &RenderContext, hours: f64) -> String {
+    ctx.locale.message(
+        "mirror_last_sync",
+        &[("hours", ctx.locale.decimal_unit(hours, 1, "hour"))],
+    )
+}
+
+/// "Err - reason" in the display locale
+pub fn error_value(ctx: &RenderContext, reason: &str) -> String {
+    format!("{} - {}", ctx.locale.message("err", &[]), reason)
+}
+
+/// Why the AUR check failed, in the display locale. JSON uses
+/// [`AurStatus::error`] instead.
+pub fn aur_error(ctx: &RenderContext, status: AurStatus) -> Option<String> {
+    match status {
+        AurStatus::Upgradable(_) => None,
+        AurStatus::Unreachable => Some(ctx.locale.message("aur_unreachable", &[])),
+        AurStatus::InvalidSignature => Some(ctx.locale.message("invalid_signature", &[])),
+    }
+}
+
+/// "upgraded", "installed", ... for recent_packages
+fn change_text(ctx: &RenderContext, change: &PackageChange) -> String {
+    ctx.locale
+        .message(&format!("change_{}", change.as_str()), &[])
+}
+
+/// "invalid signature: core, extra" for the sync dbs that failed to verify
+pub fn invalid_signature_repos(stats: &PacmanStats, ctx: &RenderContext) -> String {
+    ctx.locale.message(
+        "invalid_signature_repos",
+        &[("repos", stats.invalid_repos.join(", "))],
+    )
+}
+
+/// Total upgradable count, with a per-source breakdown such as
+/// `42 (37 repo, 5 AUR)` when AUR or Flatpak updates were counted
+fn format_upgradable(stats: &PacmanStats, ctx: &RenderContext) -> Option<String> {
+    if !stats.invalid_repos.is_empty() {
+        return Some(error_value(ctx, &invalid_signature_repos(stats, ctx)));
+    }
+    let Some(repo) = stats.total_upgradable else {
+        return stats
+            .collected
+            .contains(&StatId::Upgradable)
+            .then(|| error_value(ctx, &ctx.locale.message("upgrade_check_failed", &[])));
+    };
+
+    let num = |n: u32| ctx.locale.integer(n as i64);
+    let mut total = repo;
+    let mut sources = vec![
+        ctx.locale
+            .message("upgradable_repo", &[("count", num(repo))]),
+    ];
+    let mut notes = Vec::new();
+
+    match stats.aur_upgradable {
+        Some(AurStatus::Upgradable(aur)) if aur > 0 => {
+            total += aur;
+            sources.push(ctx.locale.message("upgradable_aur", &[("count", num(aur))]));
+        }
+        Some(AurStatus::Unreachable) => notes.push(ctx.locale.message("aur_unreachable", &[])),
+        Some(AurStatus::InvalidSignature) => {
+            notes.push(ctx.locale.message("aur_invalid_signature", &[]))
+        }
+        _ => {}
+    }
+
+    if ctx.flatpak_in_upgradable {
+        match &stats.flatpak_upgradable {
+            Some(fp) if fp.count() > 0 => {
+                total += fp.count();
+                sources.push(
+                    ctx.locale
+                        .message("upgradable_flatpak", &[("count", num(fp.count()))]),
+                );
+            }
+            None if stats.collected.contains(&StatId::FlatpakUpgradable) => {
+                notes.push(ctx.locale.message("flatpak_check_failed", &[]))
+            }
+            _ => {}
+        }
+    }
+
+    let details: Vec<String> = if sources.len() > 1 {
+        sources.into_iter().chain(notes).collect()
+    } else {
+        notes
+    };
+
+    if details.is_empty() {
+        Some(num(total))
+    } else {
+        Some(format!("{} ({})", num(total), details.join(", ")))
+    }
 }
 
 // --- stat fetch request helpers ---
@@ -186,10 +1209,66 @@ pub fn needs_upgrade_stats(requested: &[StatIdOrTitle]) -> bool {
                 | StatIdOrTitle::Stat(StatId::DownloadSize)
                 | StatIdOrTitle::Stat(StatId::InstalledSize)
                 | StatIdOrTitle::Stat(StatId::NetUpgradeSize)
+                | StatIdOrTitle::Stat(StatId::VulnerablePackages)
+                | StatIdOrTitle::Stat(StatId::Keyring)
+        )
+    })
+}
+
+pub fn needs_installed_stats(requested: &[StatIdOrTitle]) -> bool {
+    requested.iter().any(|s| {
+        matches!(
+            s,
+            StatIdOrTitle::Stat(StatId::Installed)
+                | StatIdOrTitle::Stat(StatId::ExplicitInstalled)
+                | StatIdOrTitle::Stat(StatId::DependencyInstalled)
         )
     })
 }
 
+pub fn needs_aur_stats(requested: &[StatIdOrTitle]) -> bool {
+    requested
+        .iter()
+        .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::AurUpgradable)))
+}
+
+pub fn needs_flatpak_stats(requested: &[StatIdOrTitle], include_in_upgradable: bool) -> bool {
+    requested.iter().any(|s| {
+        matches!(s, StatIdOrTitle::Stat(StatId::FlatpakUpgradable))
+            || (include_in_upgradable && matches!(s, StatIdOrTitle::Stat(StatId::Upgradable)))
+    })
+}
+
+pub fn needs_security_stats(requested: &[StatIdOrTitle]) -> bool {
+    requested
+        .iter()
+        .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::VulnerablePackages)))
+}
+
+pub fn needs_largest_packages(requested: &[StatIdOrTitle]) -> bool {
+    requested
+        .iter()
+        .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::LargestPackages)))
+}
+
+pub fn needs_recent_packages(requested: &[StatIdOrTitle]) -> bool {
+    requested
+        .iter()
+        .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::RecentPackages)))
+}
+
+pub fn needs_keyring_stat(requested: &[StatIdOrTitle]) -> bool {
+    requested
+        .iter()
+        .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::Keyring)))
+}
+
+pub fn needs_db_lock_stat(requested: &[StatIdOrTitle]) -> bool {
+    requested
+        .iter()
+        .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::DbLock)))
+}
+
 pub fn needs_orphan_stats(requested: &[StatIdOrTitle]) -> bool {
     requested
         .iter()
@@ -214,3 +1293,260 @@ pub fn needs_disk_stat(requested: &[StatIdOrTitle]) -> bool {
         .iter()
         .any(|s| matches!(s, StatIdOrTitle::Stat(StatId::Disk)))
 }
+
+/// Names of the requested custom stats
+pub fn requested_custom_stats(requested: &[StatIdOrTitle]) -> Vec<&str> {
+    requested
+        .iter()
+        .filter_map(|s| match s {
+            StatIdOrTitle::Custom(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> RenderContext {
+        RenderContext::new(&Config::default())
+    }
+
+    #[test]
+    fn test_format_templates() {
+        let stats = PacmanStats {
+            orphaned_packages: Some(3),
+            orphaned_size_bytes: Some(13107200),
+            mirror_url: Some("https://mirror.example".to_string()),
+            ..Default::default()
+        };
+        let ctx = RenderContext {
+            formats: [
+                (
+                    StatId::OrphanedPackages,
+                    "{count} pkgs / {size}".to_string(),
+                ),
+                (StatId::MirrorHealth, "synced {hours}h ago".to_string()),
+            ]
+            .into(),
+            ..ctx()
+        };
+        assert_eq!(
+            StatId::OrphanedPackages.format_value(&stats, &ctx).unwrap(),
+            "3 pkgs / 12.50 MiB"
+        );
+        // Error values keep their built-in text
+        assert_eq!(
+            StatId::MirrorHealth.format_value(&stats, &ctx).unwrap(),
+            "Err - could not check sync status"
+        );
+    }
+
+    #[test]
+    fn test_sizes_scale_and_json_carries_bytes() {
+        let stats = PacmanStats {
+            download_size_bytes: Some(512),
+            cache_size_bytes: Some(25769803776),
+            ..Default::default()
+        };
+        let value = |id: StatId, ctx: &RenderContext| id.format_value(&stats, ctx).unwrap();
+        assert_eq!(value(StatId::DownloadSize, &ctx()), "512 B");
+        assert_eq!(value(StatId::CacheSize, &ctx()), "24.00 GiB");
+        let json = StatId::CacheSize.json_value(&stats, &ctx()).unwrap();
+        assert_eq!(json["bytes"], 25769803776u64);
+
+        let ctx = RenderContext {
+            size: SizeConfig {
+                units: crate::config::SizeUnits::Si,
+                precision: 1,
+            },
+            ..ctx()
+        };
+        assert_eq!(value(StatId::CacheSize, &ctx), "25.8 GB");
+    }
+
+    #[test]
+    fn test_error_and_state_values_follow_locale() {
+        use crate::pacman::{KeyringStatus, RecentPackage, VulnerablePackage, VulnerablePackages};
+
+        let ctx = RenderContext {
+            locale: Locale::load("de"),
+            flatpak_in_upgradable: true,
+            ..ctx()
+        };
+        let vuln = |fixed_version: Option<&str>, fixed_by_upgrade| VulnerablePackage {
+            name: "openssl".to_string(),
+            installed_version: "3.0".to_string(),
+            severity: "High".to_string(),
+            fixed_version: fixed_version.map(str::to_string),
+            fixed_by_upgrade,
+        };
+        let recent = |change| RecentPackage {
+            name: "linux".to_string(),
+            version: "6.1".to_string(),
+            change,
+            seconds_ago: 60,
+        };
+        let keyring = |available: Option<&str>| KeyringStatus {
+            installed: "20240101".to_string(),
+            available: available.map(str::to_string),
+        };
+        let cases = [
+            (
+                StatId::Upgradable,
+                PacmanStats {
+                    total_upgradable: Some(1),
+                    aur_upgradable: Some(AurStatus::Unreachable),
+                    collected: vec![StatId::FlatpakUpgradable],
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::Upgradable,
+                PacmanStats {
+                    total_upgradable: Some(1),
+                    aur_upgradable: Some(AurStatus::InvalidSignature),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::Upgradable,
+                PacmanStats {
+                    collected: vec![StatId::Upgradable],
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::Upgradable,
+                PacmanStats {
+                    invalid_repos: vec!["core".to_string()],
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::AurUpgradable,
+                PacmanStats {
+                    aur_upgradable: Some(AurStatus::Unreachable),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::AurUpgradable,
+                PacmanStats {
+                    aur_upgradable: Some(AurStatus::InvalidSignature),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::FlatpakUpgradable,
+                PacmanStats {
+                    collected: vec![StatId::FlatpakUpgradable],
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::VulnerablePackages,
+                PacmanStats {
+                    vulnerable_packages: Some(VulnerablePackages {
+                        packages: vec![
+                            vuln(Some("3.1"), true),
+                            vuln(Some("3.2"), false),
+                            vuln(None, false),
+                        ],
+                    }),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::RecentPackages,
+                PacmanStats {
+                    recent_packages: Some(vec![
+                        recent(PackageChange::Installed),
+                        recent(PackageChange::Upgraded),
+                        recent(PackageChange::Downgraded),
+                        recent(PackageChange::Reinstalled),
+                    ]),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::Keyring,
+                PacmanStats {
+                    keyring: Some(keyring(Some("20240201"))),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::Keyring,
+                PacmanStats {
+                    keyring: Some(keyring(None)),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::DbLock,
+                PacmanStats {
+                    db_lock: Some(DbLockStatus::Unlocked),
+                    ..Default::default()
+                },
+            ),
+            (
+                StatId::MirrorHealth,
+                PacmanStats {
+                    mirror_url: Some("https://mirror.example".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (StatId::MirrorHealth, PacmanStats::default()),
+            (
+                StatId::CacheSize,
+                PacmanStats {
+                    timed_out: vec![StatId::CacheSize],
+                    ..Default::default()
+                },
+            ),
+        ];
+        let custom = PacmanStats {
+            custom: [
+                ("a".to_string(), CustomOutput::Failed("exit 1".to_string())),
+                ("b".to_string(), CustomOutput::TimedOut),
+            ]
+            .into(),
+            ..Default::default()
+        };
+
+        let mut rendered: Vec<String> = cases
+            .iter()
+            .flat_map(|(id, stats)| {
+                let rows = id.format_rows(stats, &ctx).unwrap_or_default();
+                id.format_value(stats, &ctx).into_iter().chain(rows)
+            })
+            .collect();
+        rendered.extend(["a", "b"].map(|name| format_custom_value(name, &custom, &ctx).unwrap()));
+
+        let english = [
+            "Err",
+            "timed out",
+            "unreachable",
+            "invalid signature",
+            "check failed",
+            "fixed by",
+            "fix pending",
+            "no fix",
+            "installed",
+            "upgraded",
+            "downgraded",
+            "Outdated",
+            "Up to date",
+            "Unlocked",
+            "sync status",
+            "no mirror",
+        ];
+        for value in &rendered {
+            for word in english {
+                assert!(!value.contains(word), "{:?} contains {:?}", value, word);
+            }
+        }
+    }
+}
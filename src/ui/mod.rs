@@ -1,10 +1,14 @@
 mod ascii;
 
 use crate::color::parse_color;
-use crate::config::{Config, PaletteConfig, TitleAlign, TitleConfig, TitleStyle, TitleWidth};
-use crate::pacman::PacmanStats;
-use crate::stats::{PaletteVariant, StatId, StatIdOrTitle};
+use crate::config::{
+    Config, PaletteConfig, Thresholds, TitleAlign, TitleConfig, TitleStyle, TitleWidth,
+};
+use crate::locale::Locale;
+use crate::pacman::{CustomOutput, PacmanStats};
+use crate::stats::{PaletteVariant, RenderContext, StatId, StatIdOrTitle, format_custom_value};
 use crossterm::style::{Color::*, Stylize};
+use std::collections::HashMap;
 use std::io;
 
 /// Calculate the minimum width needed for a title based on its style
@@ -180,7 +184,7 @@ pub fn resolve_title_text(title_config: &TitleConfig, pacman_version: &Option<St
     }
 }
 
-fn resolve_label(stat_id: &StatId, config: &Config) -> String {
+fn resolve_label(stat_id: &StatId, config: &Config, locale: &Locale) -> String {
     config
         .display
         .labels
@@ -188,13 +192,26 @@ fn resolve_label(stat_id: &StatId, config: &Config) -> String {
         .cloned()
         .unwrap_or_else(|| {
             if *stat_id == StatId::Disk {
-                format!("Disk ({})", config.disk.path)
+                format!("{} ({})", locale.label(*stat_id), config.disk.path)
             } else {
-                stat_id.label().to_string()
+                locale.label(*stat_id)
             }
         })
 }
 
+/// `[display.labels]` entry for `custom.<name>`, else the stat's own label
+fn resolve_custom_label(name: &str, config: &Config) -> String {
+    config
+        .display
+        .labels
+        .get(&format!("custom.{}", name))
+        .cloned()
+        .unwrap_or_else(|| match config.display.custom.get(name) {
+            Some(custom) if !custom.label.is_empty() => custom.label.clone(),
+            _ => name.to_string(),
+        })
+}
+
 const DARK_COLORS: [crossterm::style::Color; 8] = [
     Black,
     DarkRed,
@@ -280,18 +297,18 @@ fn render_palette_lines(
     lines
 }
 
-fn max_label_width(parsed_stats: &[StatIdOrTitle], config: &Config) -> usize {
+fn max_label_width(parsed_stats: &[StatIdOrTitle], config: &Config, locale: &Locale) -> usize {
     if !config.display.glyph.align {
         return 0;
     }
     parsed_stats
         .iter()
-        .filter_map(|s| {
-            if let StatIdOrTitle::Stat(stat_id) = s {
-                Some(resolve_label(stat_id, config).chars().count())
-            } else {
-                None
+        .filter_map(|s| match s {
+            StatIdOrTitle::Stat(stat_id) => {
+                Some(resolve_label(stat_id, config, locale).chars().count())
             }
+            StatIdOrTitle::Custom(name) => Some(resolve_custom_label(name, config).chars().count()),
+            _ => None,
         })
         .max()
         .unwrap_or(0)
@@ -310,11 +327,24 @@ fn pad_label(label: &str, width: usize) -> String {
 }
 
 pub fn display_stats(stats: &PacmanStats, config: &Config) {
+    let ctx = RenderContext::new(config);
+    for line in render_stats(stats, &ctx, config, &config.display.parsed_stats()) {
+        println!("{}", line);
+    }
+}
+
+/// The stat lines without ASCII art or image, as `--debug` prints them
+pub fn render_stats(
+    stats: &PacmanStats,
+    ctx: &RenderContext,
+    config: &Config,
+    parsed_stats: &[StatIdOrTitle],
+) -> Vec<String> {
+    let mut lines = Vec::new();
     let glyph = &config.display.glyph.glyph;
-    let parsed_stats = config.display.parsed_stats();
-    let label_width = max_label_width(&parsed_stats, config);
+    let label_width = max_label_width(parsed_stats, config, &ctx.locale);
 
-    for stat_ref in &parsed_stats {
+    for stat_ref in parsed_stats {
         match stat_ref {
             StatIdOrTitle::LegacyTitle => {
                 crate::log::warn(
@@ -322,8 +352,8 @@ pub fn display_stats(stats: &PacmanStats, config: &Config) {
                 );
                 let title_text = resolve_title_text(&config.display.title, &stats.pacman_version);
                 let dashes = "-".repeat(title_text.chars().count());
-                println!("{}", title_text);
-                println!("{}", dashes);
+                lines.push(title_text);
+                lines.push(dashes);
             }
             StatIdOrTitle::NamedTitle(name) => {
                 if let Some(title_config) = config.display.titles.get(name) {
@@ -334,30 +364,35 @@ pub fn display_stats(stats: &PacmanStats, config: &Config) {
                         TitleWidth::Fixed(w) => *w,
                     };
                     let title_lines = render_title(title_config, &title_text, width, None, None);
-                    for line in title_lines {
-                        println!("{}", line);
-                    }
+                    lines.extend(title_lines);
                 } else {
                     crate::log::warn(&format!("Title '{}' not found in config", name));
                 }
             }
             StatIdOrTitle::Stat(stat_id) => {
-                if let Some(value) = stat_id.format_value(stats) {
-                    let label = pad_label(&resolve_label(stat_id, config), label_width);
-                    println!("{}{}{}", label, glyph, value);
+                if let Some(value) = stat_id.format_value(stats, ctx) {
+                    let label =
+                        pad_label(&resolve_label(stat_id, config, &ctx.locale), label_width);
+                    lines.push(format!("{}{}{}", label, glyph, value));
+                }
+                for row in stat_id.format_rows(stats, ctx).unwrap_or_default() {
+                    lines.push(format!("{}{}", STAT_ROW_INDENT, row));
                 }
             }
-            StatIdOrTitle::ColorPalette(variant) => {
-                let palette_lines = render_palette_lines(*variant, &config.display.palette, 0);
-                for line in palette_lines {
-                    println!("{}", line);
+            StatIdOrTitle::Custom(name) => {
+                if let Some(value) = format_custom_value(name, stats, ctx) {
+                    let label = pad_label(&resolve_custom_label(name, config), label_width);
+                    lines.push(format!("{}{}{}", label, glyph, value));
                 }
             }
-            StatIdOrTitle::Newline => {
-                println!();
+            StatIdOrTitle::ColorPalette(variant) => {
+                let palette_lines = render_palette_lines(*variant, &config.display.palette, 0);
+                lines.extend(palette_lines);
             }
+            StatIdOrTitle::Newline => lines.push(String::new()),
         }
     }
+    lines
 }
 
 fn try_render_with_image(stats_lines: Vec<String>, config: &Config) -> Option<String> {
@@ -442,11 +477,13 @@ fn fix_image_cursor_up(output: String) -> String {
 }
 
 pub fn display_stats_with_graphics(stats: &PacmanStats, config: &Config) -> io::Result<()> {
+    let ctx = &RenderContext::new(config);
     let ascii_art = ascii::get_art(&config.display.ascii);
     let ascii_color = parse_color(&config.display.ascii_color);
     let glyph = &config.display.glyph.glyph;
     let parsed_stats = config.display.parsed_stats();
-    let label_width = max_label_width(&parsed_stats, config);
+    let label_width = max_label_width(&parsed_stats, config, &ctx.locale);
+    let thresholds = config.display.parsed_thresholds();
 
     // === PASS 1: Calculate content width ===
     let mut content_max_width: usize = 0;
@@ -489,13 +526,24 @@ pub fn display_stats_with_graphics(stats: &PacmanStats, config: &Config) -> io::
             }
             StatIdOrTitle::Stat(stat_id) => {
                 let value = stat_id
-                    .format_value(stats)
+                    .format_value(stats, ctx)
                     .unwrap_or_else(|| "-".to_string());
-                let label = pad_label(&resolve_label(stat_id, config), label_width);
+                let label = pad_label(&resolve_label(stat_id, config, &ctx.locale), label_width);
                 let line = format!("{}{}{}", label, glyph, value);
                 content_max_width = content_max_width.max(line.chars().count());
+                for row in stat_id.format_rows(stats, ctx).unwrap_or_default() {
+                    let row_width = STAT_ROW_INDENT.len() + row.chars().count();
+                    content_max_width = content_max_width.max(row_width);
+                }
                 stat_lines_raw.push((*stat_id, line));
             }
+            StatIdOrTitle::Custom(name) => {
+                if let Some(value) = format_custom_value(name, stats, ctx) {
+                    let label = pad_label(&resolve_custom_label(name, config), label_width);
+                    let line = format!("{}{}{}", label, glyph, value);
+                    content_max_width = content_max_width.max(line.chars().count());
+                }
+            }
             StatIdOrTitle::ColorPalette(_) => {
                 let row_width = palette_row_width(
                     &config.display.palette.style,
@@ -543,14 +591,36 @@ pub fn display_stats_with_graphics(stats: &PacmanStats, config: &Config) -> io::
                     stat_idx += 1;
 
                     // Format with colors for display
-                    let formatted =
-                        format_stat_with_colors(*raw_stat_id, stats, config, glyph, label_width);
+                    let formatted = format_stat_with_colors(
+                        *raw_stat_id,
+                        stats,
+                        ctx,
+                        config,
+                        glyph,
+                        label_width,
+                        &thresholds,
+                    );
                     // Indent stat lines based on content padding
                     if content_padding > 0 {
                         stats_lines.push(format!("{}{}", " ".repeat(content_padding), formatted));
                     } else {
                         stats_lines.push(formatted);
                     }
+                    for row in format_stat_rows(*raw_stat_id, stats, ctx, config) {
+                        stats_lines.push(format!(
+                            "{}{}{}",
+                            " ".repeat(content_padding),
+                            STAT_ROW_INDENT,
+                            row
+                        ));
+                    }
+                }
+            }
+            StatIdOrTitle::Custom(name) => {
+                if let Some(formatted) =
+                    format_custom_with_colors(name, stats, ctx, config, glyph, label_width)
+                {
+                    stats_lines.push(format!("{}{}", " ".repeat(content_padding), formatted));
                 }
             }
             StatIdOrTitle::ColorPalette(variant) => {
@@ -613,97 +683,227 @@ pub fn display_stats_with_graphics(stats: &PacmanStats, config: &Config) -> io::
     Ok(())
 }
 
-// Helper to format a stat with colors
-fn format_stat_with_colors(
+/// Indent for the rows of list stats like largest_packages
+const STAT_ROW_INDENT: &str = "  ";
+
+/// Rows of a list stat, colored like the stat's value
+fn format_stat_rows(
     stat_id: StatId,
     stats: &PacmanStats,
+    ctx: &RenderContext,
     config: &Config,
-    glyph: &str,
-    label_pad_width: usize,
-) -> String {
-    let label = pad_label(&resolve_label(&stat_id, config), label_pad_width);
+) -> Vec<String> {
     let colors = &config.display.colors;
-    let key = stat_id.config_key();
+    let value_color = parse_color(
+        colors
+            .overrides
+            .get(stat_id.config_key())
+            .and_then(|o| o.stat.as_deref())
+            .unwrap_or(&colors.stat),
+    );
+    stat_id
+        .format_rows(stats, ctx)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| match value_color {
+            Some(c) => format!("{}", row.with(c)),
+            None => row,
+        })
+        .collect()
+}
 
-    // Resolve label color: per-stat override > global
+/// Bold label in its per-stat override color, else the global label color
+fn color_label(label: &str, key: &str, config: &Config) -> String {
+    let colors = &config.display.colors;
     let label_color_str = colors
         .overrides
         .get(key)
         .and_then(|o| o.label.as_deref())
         .unwrap_or(&colors.label);
-    let label_color = parse_color(label_color_str);
-    let colored_label = match label_color {
+    match parse_color(label_color_str) {
         Some(c) => format!("{}", label.bold().with(c)),
         None => format!("{}", label.bold()),
-    };
+    }
+}
 
-    // glyph color
-    let glyph_color = parse_color(&config.display.glyph.color);
-    let colored_glyph = match glyph_color {
+fn color_glyph(glyph: &str, config: &Config) -> String {
+    match parse_color(&config.display.glyph.color) {
         Some(c) => format!("{}", glyph.with(c)),
         None => glyph.to_string(),
+    }
+}
+
+/// A custom stat line, colored through the `custom.<name>` overrides like
+/// built-in stats. None if the stat was never run.
+fn format_custom_with_colors(
+    name: &str,
+    stats: &PacmanStats,
+    ctx: &RenderContext,
+    config: &Config,
+    glyph: &str,
+    label_pad_width: usize,
+) -> Option<String> {
+    let output = stats.custom.get(name)?;
+    let label = pad_label(&resolve_custom_label(name, config), label_pad_width);
+    let colors = &config.display.colors;
+    let key = format!("custom.{}", name);
+    let colored_label = color_label(&label, &key, config);
+    let colored_glyph = color_glyph(glyph, config);
+    let val_override = colors.overrides.get(&key).and_then(|o| o.stat.as_deref());
+
+    let value = match output {
+        CustomOutput::Failed(reason) => {
+            let err = ctx.locale.message("err", &[]);
+            let err = match val_override.and_then(parse_color) {
+                Some(c) => format!("{}", err.with(c)),
+                None => format!("{}", err.red()),
+            };
+            format!("{} - {}", err, reason)
+        }
+        _ => {
+            let value = format_custom_value(name, stats, ctx)?;
+            match parse_color(val_override.unwrap_or(&colors.stat)) {
+                Some(c) => format!("{}", value.with(c)),
+                None => value,
+            }
+        }
     };
+    Some(format!("{}{}{}", colored_label, colored_glyph, value))
+}
 
-    if stat_id == StatId::MirrorHealth {
-        let val_override = colors
-            .overrides
-            .get(key)
-            .and_then(|o| o.stat.as_deref())
-            .and_then(parse_color);
+// Helper to format a stat with colors
+fn format_stat_with_colors(
+    stat_id: StatId,
+    stats: &PacmanStats,
+    ctx: &RenderContext,
+    config: &Config,
+    glyph: &str,
+    label_pad_width: usize,
+    thresholds: &HashMap<StatId, Thresholds>,
+) -> String {
+    let label = pad_label(
+        &resolve_label(&stat_id, config, &ctx.locale),
+        label_pad_width,
+    );
+    let colors = &config.display.colors;
+    let key = stat_id.config_key();
+    let colored_label = color_label(&label, key, config);
+    let colored_glyph = color_glyph(glyph, config);
+
+    // Timed out and templated stats skip the semantic rendering below, except
+    // for the disk percentage
+    let timed_out = stats.timed_out.contains(&stat_id);
+    let templated = ctx.formats.contains_key(&stat_id);
+    let val_override = colors
+        .overrides
+        .get(key)
+        .and_then(|o| o.stat.as_deref())
+        .and_then(parse_color);
+    // Per-stat value override > threshold rule > global stat color
+    let rule = thresholds.get(&stat_id);
+    let threshold_color = stat_id
+        .metric(stats, ctx)
+        .and_then(|value| rule?.color_for(value))
+        .and_then(parse_color);
+    let err_color = val_override
+        .or_else(|| rule.and_then(|r| parse_color(&r.critical_color)))
+        .unwrap_or(Red);
+    let err = ctx.locale.message("err", &[]);
+
+    if stat_id == StatId::MirrorHealth
+        && !timed_out
+        && !(templated && stats.mirror_url.is_some() && stats.mirror_sync_age_hours.is_some())
+    {
         let value_str = match (&stats.mirror_url, stats.mirror_sync_age_hours) {
-            (Some(_), Some(age)) => match val_override {
-                Some(c) => format!("{} (last sync {:.1} hours)", "OK".with(c), age),
-                None => format!("{} (last sync {:.1} hours)", "OK".green(), age),
-            },
-            (Some(_), None) => match val_override {
-                Some(c) => format!("{} - could not check sync status", "Err".with(c)),
-                None => format!("{} - could not check sync status", "Err".red()),
-            },
-            (None, _) => match val_override {
-                Some(c) => format!("{} - no mirror found", "Err".with(c)),
-                None => format!("{} - no mirror found", "Err".red()),
-            },
+            (Some(_), Some(age)) => {
+                let ok = ctx.locale.message("mirror_ok", &[]);
+                let last_sync = crate::stats::mirror_last_sync(ctx, age);
+                match val_override.or(threshold_color) {
+                    Some(c) => format!("{} ({})", ok.with(c), last_sync),
+                    None => format!("{} ({})", ok, last_sync),
+                }
+            }
+            (Some(_), None) => format!(
+                "{} - {}",
+                err.with(err_color),
+                ctx.locale.message("mirror_unchecked", &[])
+            ),
+            (None, _) => format!(
+                "{} - {}",
+                err.with(err_color),
+                ctx.locale.message("mirror_not_found", &[])
+            ),
         };
         format!("{}{}{}", colored_label, colored_glyph, value_str)
-    } else if stat_id == StatId::Disk {
-        // Per-stat value override replaces semantic percentage colors
-        let val_override = colors
-            .overrides
-            .get(key)
-            .and_then(|o| o.stat.as_deref())
-            .and_then(parse_color);
-        if let (Some(used), Some(total)) = (stats.disk_used_bytes, stats.disk_total_bytes) {
-            let used_gib = used as f64 / 1073741824.0;
-            let total_gib = total as f64 / 1073741824.0;
+    } else if stat_id == StatId::AurUpgradable
+        && !timed_out
+        && let Some(error) = stats
+            .aur_upgradable
+            .and_then(|status| crate::stats::aur_error(ctx, status))
+    {
+        format!(
+            "{}{}{} - {}",
+            colored_label,
+            colored_glyph,
+            err.with(err_color),
+            error
+        )
+    } else if stat_id == StatId::FlatpakUpgradable
+        && !timed_out
+        && stats.flatpak_upgradable.is_none()
+        && stats.collected.contains(&StatId::FlatpakUpgradable)
+    {
+        format!(
+            "{}{}{} - {}",
+            colored_label,
+            colored_glyph,
+            err.with(err_color),
+            ctx.locale.message("flatpak_check_failed", &[])
+        )
+    } else if stat_id == StatId::Upgradable && !timed_out && !stats.invalid_repos.is_empty() {
+        format!(
+            "{}{}{} - {}",
+            colored_label,
+            colored_glyph,
+            err.with(err_color),
+            crate::stats::invalid_signature_repos(stats, ctx)
+        )
+    } else if stat_id == StatId::Disk && !timed_out {
+        // Only the percentage takes the threshold color, templated or not
+        let color_pct = |pct: &str| match val_override.or(threshold_color) {
+            Some(c) => format!("{}", pct.with(c)),
+            None => pct.to_string(),
+        };
+        let value = if templated {
+            stat_id.format_template_with(stats, ctx, "percent", color_pct)
+        } else if let (Some(used), Some(total)) = (stats.disk_used_bytes, stats.disk_total_bytes) {
             let pct = if total > 0 {
                 (used as f64 / total as f64) * 100.0
             } else {
                 0.0
             };
-            let pct_str = format!("({:.0}%)", pct);
-            let colored_pct = match val_override {
-                Some(c) => format!("{}", pct_str.with(c)),
-                None if pct > 90.0 => format!("{}", pct_str.red()),
-                None if pct >= 70.0 => format!("{}", pct_str.yellow()),
-                None => format!("{}", pct_str.green()),
-            };
-            format!(
-                "{}{}{:.2} GiB / {:.2} GiB {}",
-                colored_label, colored_glyph, used_gib, total_gib, colored_pct
-            )
+            Some(format!(
+                "{} / {} {}",
+                crate::util::format_size(used as i64, &ctx.size, &ctx.locale),
+                crate::util::format_size(total as i64, &ctx.size, &ctx.locale),
+                color_pct(&format!("({:.0}%)", pct))
+            ))
         } else {
-            format!("{}{}-", colored_label, colored_glyph)
-        }
+            None
+        };
+        format!(
+            "{}{}{}",
+            colored_label,
+            colored_glyph,
+            value.unwrap_or_else(|| "-".to_string())
+        )
     } else {
         let value = stat_id
-            .format_value(stats)
+            .format_value(stats, ctx)
             .unwrap_or_else(|| "-".to_string());
-        let value_color_str = colors
-            .overrides
-            .get(key)
-            .and_then(|o| o.stat.as_deref())
-            .unwrap_or(&colors.stat);
-        let value_color = parse_color(value_color_str);
+        let value_color = val_override
+            .or(threshold_color)
+            .or_else(|| parse_color(&colors.stat));
         let colored_value = match value_color {
             Some(c) => format!("{}", value.with(c)),
             None => value,
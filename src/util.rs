@@ -1,36 +1,63 @@
-use chrono::Local;
+use crate::config::{SizeConfig, SizeUnits, TimestampFormat};
+use crate::locale::Locale;
+use chrono::{DateTime, Local};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
 use std::time::Duration;
 
-/// Convert seconds to a human-readable duration string
-pub fn normalize_duration(seconds: i64) -> String {
-    if seconds < 60 {
-        return format!("{} second{}", seconds, if seconds != 1 { "s" } else { "" });
-    }
+/// Scale a byte count to the largest unit it reaches, e.g. `1.50 GiB`,
+/// or `1.61 GB` with SI units. Plain bytes have no decimals.
+pub fn format_size(bytes: i64, size: &SizeConfig, locale: &Locale) -> String {
+    let (base, units) = match size.units {
+        SizeUnits::Iec => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB"]),
+        SizeUnits::Si => (1000.0, ["B", "kB", "MB", "GB", "TB"]),
+    };
 
-    if seconds < 3600 {
-        let minutes = seconds / 60;
-        return format!("{} minute{}", minutes, if minutes != 1 { "s" } else { "" });
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value.abs() >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
     }
 
-    if seconds < 86400 {
-        let hours = seconds / 3600;
-        return format!("{} hour{}", hours, if hours != 1 { "s" } else { "" });
+    if unit == 0 {
+        format!("{} {}", locale.integer(bytes), units[0])
+    } else {
+        format!("{} {}", locale.decimal(value, size.precision), units[unit])
     }
+}
 
-    let days = seconds / 86400;
-    let hours = (seconds % 86400) / 3600;
+/// The `absolute` timestamp format
+pub const ABSOLUTE_TIMESTAMP: &str = "%Y-%m-%d %H:%M";
 
-    format!(
-        "{} day{} {} hour{}",
-        days,
-        if days != 1 { "s" } else { "" },
-        hours,
-        if hours != 1 { "s" } else { "" }
-    )
+/// Format a timestamp stat, relative to now where the format asks for it
+pub fn format_timestamp(
+    time: DateTime<Local>,
+    format: &TimestampFormat,
+    locale: &Locale,
+) -> String {
+    let seconds = Local::now()
+        .signed_duration_since(time)
+        .num_seconds()
+        .max(0);
+    match format {
+        TimestampFormat::Relative => locale.duration(seconds),
+        TimestampFormat::Compact => locale.compact_duration(seconds),
+        TimestampFormat::Absolute => time.format(ABSOLUTE_TIMESTAMP).to_string(),
+        TimestampFormat::Both => format!(
+            "{} ({})",
+            time.format(ABSOLUTE_TIMESTAMP),
+            locale.duration(seconds)
+        ),
+        TimestampFormat::Custom(pattern) => time.format(pattern).to_string(),
+    }
+}
+
+/// Convert seconds to a human-readable duration string, in English
+pub fn normalize_duration(seconds: i64) -> String {
+    Locale::default().duration(seconds)
 }
 
 /// Create a spinner with the given message
@@ -135,3 +162,29 @@ pub fn log_error(msg: &str, debug: bool) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_formats() {
+        let locale = Locale::default();
+        let time = Local::now() - chrono::Duration::seconds(3 * 86400 + 4 * 3600 + 30);
+        let format =
+            |f: &str| format_timestamp(time, &TimestampFormat::parse(f, "last_update"), &locale);
+
+        assert_eq!(format("relative"), "3 days 4 hours");
+        assert_eq!(format("compact"), "3d 4h");
+        assert_eq!(
+            format("absolute"),
+            time.format("%Y-%m-%d %H:%M").to_string()
+        );
+        assert!(format("both").ends_with(" (3 days 4 hours)"));
+        assert_eq!(format("%Y"), time.format("%Y").to_string());
+        assert_eq!(
+            TimestampFormat::parse("%Q", "last_update"),
+            TimestampFormat::Relative
+        );
+    }
+}